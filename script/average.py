@@ -0,0 +1,22 @@
+import json
+import time
+import zsak
+
+# Averages the temperature of the rooms, the readings being cached for a few
+# seconds between queries.
+cached = zsak.state.get("readings")
+if cached is None or time.time() - cached[0] > 5:
+    readings = zsak.get("demo/rooms/*/temperature", timeout=2.0)
+    zsak.state["readings"] = (time.time(), readings)
+    zsak.log(f"refreshed {len(readings)} readings")
+else:
+    readings = cached[1]
+
+values = [float(value) for _, value in readings]
+if values:
+    average = sum(values) / len(values)
+    zsak.put("demo/stats/temperature", str(average))
+    result = json.dumps({"average": average, "rooms": len(values)})
+    result_encoding = "application/json"
+else:
+    error = "No temperature available"
@@ -1,21 +1,46 @@
 use crate::parser::*;
+use crate::aggregate::{Aggregate, Aggregation};
+use crate::selector::{parse_parameter, time_range, unescape};
+use crate::serve::{encoding_for, join_key, list_files, parse_data};
+use crate::snapshot::{SnapshotEntry, key_dir, read_snapshot, rewrite_prefix, write_snapshot};
+use crate::analysis::{QueryAnalysis, ReplyErrorRecord, ReplyRecord};
+use crate::command::CommandRuntime;
+use crate::hook::SampleHook;
+use crate::fault::{Delay, happens, parse_rate};
+use crate::mock::{parse_rules, render};
+use crate::script::{ScriptEngine, ScriptInput, ScriptOutput, ScriptRuntime};
+use crate::transform::Transform;
+use crate::tree::KeyTree;
 use crate::types::*;
 
 use clap::ArgMatches;
 use colored::Colorize;
-use std::any::Any;
 use std::borrow::Cow;
-use std::collections::HashMap;
-use std::time::Duration;
-use zenoh::bytes::Encoding;
+use std::collections::{BTreeMap, HashMap};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::AsyncBufReadExt;
+use zenoh::Result as ZResult;
+use zenoh::bytes::{Encoding, ZBytes};
 use zenoh::config::WhatAmI;
+use zenoh::handlers::FifoChannelHandler;
 use zenoh::liveliness::LivelinessToken;
-use zenoh::qos::Reliability;
-use zenoh::query::{ConsolidationMode, QueryTarget};
-use zenoh::sample::{SampleKind, SourceInfo};
+use zenoh::qos::{Priority, Reliability};
+use zenoh::query::{ConsolidationMode, Query, QueryTarget, Reply};
+use zenoh::sample::{Sample, SampleKind, SourceInfo};
 use zenoh::session::ZenohId;
+use zenoh::time::Timestamp;
 
 const LIST_SCOUTING_INTERVAL: u64 = 2;
+const DELETE_VERIFY_DELAY_MS: u64 = 500;
+const MIN_CONSISTENCY_REPLICAS: usize = 2;
+const QUERY_PROGRESS_PERIOD_MS: u64 = 250;
+const QUERYABLE_RELOAD_PERIOD_MS: u64 = 1000;
+const HOOK_TIMEOUT_MS: u64 = 10000;
+const INJECTED_ERROR: &str = "Injected error";
 
 pub async fn do_doctor() {
     match std::env::var("ZSAK_HOME") {
@@ -128,46 +153,259 @@ pub async fn do_delete(z: &zenoh::Session, sub_matches: &ArgMatches) {
         .await
         .unwrap();
 
-    z.delete(kexpr).await.unwrap();
+    if resolve_bool_argument(sub_matches, "recursive") {
+        let preview = resolve_bool_argument(sub_matches, "preview");
+        let yes = resolve_bool_argument(sub_matches, "yes");
+        do_delete_recursive(z, &kexpr, preview && !yes).await;
+    } else {
+        z.delete(kexpr).await.unwrap();
+    }
+}
+
+// Deletes one by one the concrete keys that the storages hold for the key
+// expression, then queries them again to check that they are gone.
+async fn do_delete_recursive(z: &zenoh::Session, kexpr: &str, confirm: bool) {
+    let keys: Vec<String> = do_fetch_entries(z, kexpr)
+        .await
+        .into_iter()
+        .map(|e| e.key)
+        .collect();
+    if keys.is_empty() {
+        println!("No keys found for {}", kexpr.bold());
+        return;
+    }
+
+    println!("{} keys matching {}:", keys.len(), kexpr.bold());
+    for key in &keys {
+        println!("\t{}", key);
+    }
+    if confirm {
+        print!("Delete these {} keys? [y/N] ", keys.len());
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+        let mut answer = String::new();
+        let _ = tokio::io::BufReader::new(tokio::io::stdin())
+            .read_line(&mut answer)
+            .await;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("Aborted");
+            return;
+        }
+    }
+
+    for key in &keys {
+        match z.delete(key).await {
+            Ok(()) => println!("\t{}: {}", key, "Deleted".red()),
+            Err(e) => println!("\t{}: {}", key.red(), e),
+        }
+    }
+
+    // Give storages a chance to process the deletions before checking
+    tokio::time::sleep(Duration::from_millis(DELETE_VERIFY_DELAY_MS)).await;
+    let remaining: Vec<String> = do_fetch_entries(z, kexpr)
+        .await
+        .into_iter()
+        .map(|e| e.key)
+        .filter(|k| keys.contains(k))
+        .collect();
+    if remaining.is_empty() {
+        println!("{}", "Verified: no deleted key is returned anymore".green());
+    } else {
+        println!("{}:", "Still returned after deletion".bold().red());
+        for key in remaining {
+            println!("\t{}", key);
+        }
+    }
 }
 
 pub async fn do_subscribe(z: &zenoh::Session, sub_matches: &ArgMatches) {
     let kexpr: String = resolve_argument(sub_matches, "KEY_EXPR", false)
         .await
         .unwrap();
+    let verbose = resolve_bool_argument(sub_matches, "verbose");
+    let transform = resolve_transform(sub_matches).await;
+    let mut hook = resolve_sample_hook(z, sub_matches).await;
+
+    // The subscriber is declared before issuing the initial query, so that
+    // samples published in the meantime are queued rather than lost.
+    let s = z.declare_subscriber(kexpr.clone()).await.unwrap();
+    let mut history = HashMap::<String, Timestamp>::new();
+    if resolve_bool_argument(sub_matches, "initial-query") {
+        let p = QueryParams {
+            query_expr: kexpr,
+            consolidation: ConsolidationMode::Latest,
+            ..Default::default()
+        };
+        let replies = do_get(z, &p).await.unwrap();
+        let mut h: u64 = 0;
+        while let Ok(reply) = replies.recv_async().await {
+            if let Ok(sample) = reply.result() {
+                h += 1;
+                if let Some(ts) = sample.timestamp() {
+                    history.insert(sample.key_expr().to_string(), *ts);
+                }
+                handle_sample("History", h, sample, verbose, &transform, &mut hook).await;
+            }
+        }
+    }
 
-    let s = z.declare_subscriber(kexpr).await.unwrap();
     let mut n: u64 = 0;
     while let Ok(sample) = s.recv_async().await {
+        // Skip the live samples already reported by the initial query
+        if let (Some(ts), Some(seen)) = (
+            sample.timestamp(),
+            history.get(sample.key_expr().as_str()),
+        ) && ts <= seen
+        {
+            continue;
+        }
         n += 1;
-        println!("{}({}):", "Sample".bold(), n);
-        if sample.encoding().type_id() == Encoding::ZENOH_STRING.type_id() {
-            if let Some(attch) = sample.attachment() {
-                let str = attch.try_to_string().unwrap_or(Cow::from("[..]"));
+        handle_sample("Sample", n, &sample, verbose, &transform, &mut hook).await;
+    }
+}
 
-                println!(
-                    "\t{}: {}\n\t{}: {}\n\t{}: {}\n",
-                    "key".bold(),
-                    sample.key_expr(),
-                    "value".bold(),
-                    sample.payload().try_to_string().unwrap(),
-                    "attachment".bold(),
-                    str
-                );
-            } else {
-                println!(
-                    "\t{}: {}\n\t{}: {}\n",
-                    "key".bold(),
-                    sample.key_expr(),
-                    "value".bold(),
-                    sample.payload().try_to_string().unwrap()
-                );
+async fn handle_sample(
+    label: &str,
+    n: u64,
+    sample: &Sample,
+    verbose: bool,
+    transform: &Option<Transform>,
+    hook: &mut Option<SampleHook>,
+) {
+    let meta = SampleMetadata::from(sample);
+    let ctx = SampleContext {
+        key: sample.key_expr().to_string(),
+        kind: meta.kind.to_string(),
+        timestamp: meta.timestamp.clone(),
+        encoding: meta.encoding.clone(),
+    };
+    // The transform only shapes what is printed, hooks get the raw sample
+    // even when the transform filters it out.
+    let printed = match meta.kind {
+        SampleKind::Put => {
+            let values = apply_transform(transform, &payload_to_string(sample), &ctx);
+            let printed = !values.is_empty();
+            if printed {
+                println!("{}({}): {}", label.bold(), n, "PUT".bold().green());
+                println!("\t{}: {}", "key".bold(), sample.key_expr());
+                for value in values {
+                    println!("\t{}: {}", "value".bold(), value);
+                }
+                if let Some(attch) = sample.attachment() {
+                    let str = attch.try_to_string().unwrap_or(Cow::from("[..]"));
+                    println!("\t{}: {}", "attachment".bold(), str);
+                }
             }
+            printed
+        }
+        SampleKind::Delete => {
+            println!("{}({}): {}", label.bold(), n, "DELETE".bold().red());
+            println!("\t{}: {}", "key".bold(), sample.key_expr());
+            true
+        }
+    };
+    if printed {
+        if verbose {
+            print_sample_metadata(&meta);
+        }
+        println!();
+    }
+
+    if let Some(hook) = hook {
+        let payload = sample.payload().to_bytes();
+        let attachment = sample.attachment().map(|a| a.to_bytes());
+        if let Err(e) = hook.run(&ctx, &payload, attachment.as_deref()).await {
+            println!("{}: {}", "Hook Error".bold().red(), e);
         }
     }
 }
 
-pub async fn do_query(z: &zenoh::Session, sub_matches: &ArgMatches) {
+fn print_sample_metadata(meta: &SampleMetadata) {
+    println!(
+        "\t{}: {}",
+        "timestamp".bold(),
+        meta.timestamp.as_deref().unwrap_or("None")
+    );
+    println!("\t{}: {}", "encoding".bold(), meta.encoding);
+    println!(
+        "\t{}: priority: {:?}, congestion control: {:?}, express: {}",
+        "qos".bold(),
+        meta.priority,
+        meta.congestion_control,
+        meta.express
+    );
+    println!(
+        "\t{}: {} (sn: {})",
+        "source".bold(),
+        meta.source_id.as_deref().unwrap_or("Unknown"),
+        meta.source_sn
+            .map(|sn| sn.to_string())
+            .unwrap_or_else(|| "Unknown".into())
+    );
+    println!("\t{}: {} bytes", "size".bold(), meta.size);
+}
+
+async fn resolve_sample_hook(z: &zenoh::Session, sub_matches: &ArgMatches) -> Option<SampleHook> {
+    let timeout = resolve_optional_argument::<u64>(sub_matches, "timeout", false)
+        .await
+        .expect("The timeout should be an integer")
+        .map(Duration::from_millis)
+        .unwrap_or(Duration::from_millis(HOOK_TIMEOUT_MS));
+    if let Some(cmd) = resolve_optional_argument::<String>(sub_matches, "exec", false)
+        .await
+        .unwrap()
+    {
+        if resolve_bool_argument(sub_matches, "stream") {
+            Some(SampleHook::stream(&cmd).unwrap_or_else(|e| panic!("{}", e)))
+        } else {
+            Some(SampleHook::exec(&cmd, timeout))
+        }
+    } else if let Some(path) = sub_matches.get_one::<String>("script") {
+        let engine = ScriptEngine::detect(
+            sub_matches.get_one::<String>("engine").map(String::as_str),
+            Some(path),
+        )
+        .unwrap_or_else(|e| panic!("{}", e));
+        let code: String = resolve_argument(sub_matches, "script", true).await.unwrap();
+        let packages = resolve_optional_argument::<String>(sub_matches, "packages", false)
+            .await
+            .unwrap();
+        // Samples are handled one at a time, so a single worker is enough.
+        Some(SampleHook::script(
+            ScriptRuntime::new(
+                z,
+                engine,
+                &code,
+                packages.as_deref(),
+                1,
+                timeout,
+            )
+            .unwrap_or_else(|e| panic!("{}", e)),
+        ))
+    } else {
+        None
+    }
+}
+
+async fn resolve_transform(sub_matches: &ArgMatches) -> Option<Transform> {
+    resolve_optional_argument::<String>(sub_matches, "transform", false)
+        .await
+        .unwrap()
+        .map(|expr| Transform::new(&expr).unwrap_or_else(|e| panic!("{}", e)))
+}
+
+// Returns the values to display for a payload: the payload itself when no
+// transformation is set, otherwise whatever the expression produced.
+fn apply_transform(transform: &Option<Transform>, value: &str, ctx: &SampleContext) -> Vec<String> {
+    match transform {
+        Some(t) => t.apply(value, ctx).unwrap_or_else(|e| {
+            println!("{}: {}", "Transform Error".bold().red(), e);
+            Vec::new()
+        }),
+        None => vec![value.to_string()],
+    }
+}
+
+async fn resolve_query_params(sub_matches: &ArgMatches) -> QueryParams {
     let file_based_data = resolve_bool_argument(sub_matches, "file");
 
     let qexpr: String = resolve_argument(sub_matches, "QUERY_EXPR", false)
@@ -208,47 +446,108 @@ pub async fn do_query(z: &zenoh::Session, sub_matches: &ArgMatches) {
                 ConsolidationMode::None
             }
         };
-    println!("Consolidation mode: {:?}", consolidation);
     let some_attach =
         resolve_optional_argument::<String>(sub_matches, "ATTACHMENT", file_based_data)
             .await
             .unwrap();
 
-    let replies = if body.is_none() {
-        if some_attach.is_none() {
-            z.get(qexpr)
-                .target(target)
-                .consolidation(consolidation)
-                .await
-                .unwrap()
-        } else {
-            z.get(qexpr)
-                .target(target)
-                .consolidation(consolidation)
-                .attachment(some_attach.unwrap())
-                .await
-                .unwrap()
-        }
-    } else if some_attach.is_none() {
-        z.get(qexpr)
-            .target(target)
-            .consolidation(consolidation)
-            .payload(body.unwrap())
+    let mut parameters = BTreeMap::new();
+    for param in sub_matches.get_many::<String>("param").unwrap_or_default() {
+        let (name, value) = parse_parameter(param).unwrap_or_else(|e| panic!("{}", e));
+        parameters.insert(name, value);
+    }
+    let since = sub_matches.get_one::<String>("since");
+    let until = sub_matches.get_one::<String>("until");
+    if let Some(range) = time_range(since.map(|s| s.as_str()), until.map(|s| s.as_str())) {
+        parameters.insert("_time".into(), range);
+    }
+
+    let timeout = resolve_optional_argument::<u64>(sub_matches, "timeout", false)
+        .await
+        .expect("The timeout should be an integer")
+        .map(Duration::from_millis);
+
+    QueryParams {
+        query_expr: qexpr,
+        body,
+        attachment: some_attach,
+        target,
+        consolidation,
+        parameters,
+        timeout,
+    }
+}
+
+pub async fn do_query(z: &zenoh::Session, sub_matches: &ArgMatches) {
+    let p = resolve_query_params(sub_matches).await;
+    println!("Consolidation mode: {:?}", p.consolidation);
+    if !p.parameters.is_empty() {
+        println!("Selector: {}", p.selector());
+    }
+    if let Some(period) = resolve_optional_argument::<u64>(sub_matches, "watch", false)
+        .await
+        .expect("The watch period should be an integer")
+    {
+        do_query_watch(z, &p, Duration::from_secs(period)).await;
+        return;
+    }
+    if let Some(op) = resolve_optional_argument::<Aggregate>(sub_matches, "aggregate", false)
+        .await
+        .unwrap_or_else(|e| panic!("{}", e))
+    {
+        let field = resolve_optional_argument::<String>(sub_matches, "field", false)
             .await
-            .unwrap()
-    } else {
-        z.get(qexpr)
-            .target(target)
-            .consolidation(consolidation)
-            .payload(body.unwrap())
-            .attachment(some_attach.unwrap())
+            .unwrap();
+        let group_by = resolve_optional_argument::<usize>(sub_matches, "group-by", false)
             .await
-            .unwrap()
+            .expect("The group-by chunk should be a positive integer");
+        do_query_aggregate(z, &p, op, field.as_deref(), group_by).await;
+        return;
+    }
+    let transform = resolve_transform(sub_matches).await;
+    let mut analysis = if resolve_bool_argument(sub_matches, "analyze") {
+        Some(QueryAnalysis::new())
+    } else {
+        None
     };
 
+    let start = Instant::now();
+    let replies = do_get(z, &p).await.unwrap();
+
+    // The progress line goes to stderr, and only when it is a terminal, so
+    // that it never ends up mixed with redirected results.
+    let show_progress = std::io::stderr().is_terminal();
+    let mut progress = tokio::time::interval(Duration::from_millis(QUERY_PROGRESS_PERIOD_MS));
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let mut cancelled = false;
+
     let mut count: u64 = 0;
-    while let Ok(reply) = replies.recv_async().await {
+    let mut errors: u64 = 0;
+    loop {
+        let reply = tokio::select! {
+            reply = replies.recv_async() => match reply {
+                Ok(reply) => reply,
+                Err(_) => break,
+            },
+            _ = &mut ctrl_c => {
+                cancelled = true;
+                break;
+            }
+            _ = progress.tick(), if show_progress => {
+                eprint!(
+                    "\r{} replies received, {:.1}s elapsed",
+                    count,
+                    start.elapsed().as_secs_f64()
+                );
+                continue;
+            }
+        };
+        if show_progress {
+            eprint!("\r\x1B[K");
+        }
         count += 1;
+        let latency = start.elapsed();
 
         println!("{}({}):", "Reply".bold(), count);
         let rid = if let Some(id) = reply.replier_id() {
@@ -257,6 +556,9 @@ pub async fn do_query(z: &zenoh::Session, sub_matches: &ArgMatches) {
             "Unknown".into()
         };
         println!("\t{}: {}", "Replier Id".bold(), rid);
+        if analysis.is_some() {
+            println!("\t{}: {:?}", "Latency".bold(), latency);
+        }
 
         match reply.result() {
             Ok(result) => {
@@ -277,17 +579,559 @@ pub async fn do_query(z: &zenoh::Session, sub_matches: &ArgMatches) {
                         .map(|ts| { ts.to_string() })
                         .unwrap_or_else(|| "None".into())
                 );
+                let ctx = SampleContext {
+                    key: result.key_expr().to_string(),
+                    kind: result.kind().to_string(),
+                    timestamp: result.timestamp().map(|ts| ts.to_string()),
+                    encoding: result.encoding().to_string(),
+                };
+                let value = payload_to_string(result);
+                for value in apply_transform(&transform, &value, &ctx) {
+                    println!("\t{}: {}", "Value".bold(), value);
+                }
+                if let Some(ref mut analysis) = analysis {
+                    analysis.record(ReplyRecord {
+                        replier: rid,
+                        key: result.key_expr().to_string(),
+                        value,
+                        timestamp: result.timestamp().cloned(),
+                        latency,
+                    });
+                }
+            }
+            Err(e) => {
+                errors += 1;
+                let message = bytes_to_string(e.payload());
+                println!("\t{}: {}", "Error".bold().red(), message);
+                println!("\t{}: {}", "Encoding".bold(), e.encoding());
+                if let Some(ref mut analysis) = analysis {
+                    analysis.record_error(ReplyErrorRecord {
+                        replier: rid,
+                        message,
+                        latency,
+                    });
+                }
+            }
+        }
+    }
+
+    if show_progress {
+        eprint!("\r\x1B[K");
+    }
+    let summary = format!(
+        "{} replies ({} errors) received in {:?}",
+        count - errors,
+        errors,
+        start.elapsed()
+    );
+    if cancelled {
+        println!("{}: {}", "Cancelled".bold().yellow(), summary);
+    } else {
+        println!("{}", summary);
+    }
+
+    if let Some(analysis) = analysis {
+        print_query_analysis(&analysis, start.elapsed());
+    }
+}
+
+// Runs the query and prints an aggregate of the numeric values of the
+// replies, one per group when grouping by a key chunk.
+async fn do_query_aggregate(
+    z: &zenoh::Session,
+    p: &QueryParams,
+    op: Aggregate,
+    field: Option<&str>,
+    group_by: Option<usize>,
+) {
+    let replies = do_get(z, p).await.unwrap();
+    let mut aggregation = Aggregation::new();
+    let mut errors = 0;
+    while let Ok(reply) = replies.recv_async().await {
+        match reply.result() {
+            Ok(sample) => aggregation.add(
+                sample.key_expr().as_str(),
+                &payload_to_string(sample),
+                field,
+                group_by,
+            ),
+            Err(_) => errors += 1,
+        }
+    }
+
+    println!("{}({}):", "Aggregate".bold(), op);
+    if aggregation.groups.is_empty() {
+        println!("\tNo numeric value received");
+    }
+    for (group, acc) in &aggregation.groups {
+        let label = if group_by.is_some() {
+            format!("{}: ", group.bold())
+        } else {
+            String::new()
+        };
+        println!(
+            "\t{}{} {}",
+            label,
+            acc.result(op),
+            format!("({} values)", acc.count).dimmed()
+        );
+    }
+    if !aggregation.skipped.is_empty() {
+        println!(
+            "{}: {} replies without a numeric value",
+            "Skipped".bold().yellow(),
+            aggregation.skipped.len()
+        );
+        for key in &aggregation.skipped {
+            println!("\t- {}", key);
+        }
+    }
+    if errors > 0 {
+        println!("{}: {} error replies", "Errors".bold().red(), errors);
+    }
+}
+
+// Re-issues the query every `period` through a declared querier, printing
+// only the keys that appeared, disappeared or changed since the last round.
+async fn do_query_watch(z: &zenoh::Session, p: &QueryParams, period: Duration) {
+    let selector = p.selector();
+    let (kexpr, parameters) = selector.split_once('?').unwrap_or((&selector, ""));
+    let mut querier = z
+        .declare_querier(kexpr.to_string())
+        .target(p.target)
+        .consolidation(p.consolidation);
+    if let Some(timeout) = p.timeout {
+        querier = querier.timeout(timeout);
+    }
+    let querier = querier.await.expect("Unable to declare querier");
+
+    let mut previous: Option<BTreeMap<String, String>> = None;
+    let mut matching: Option<bool> = None;
+    let mut round: u64 = 0;
+    loop {
+        round += 1;
+        let status = querier
+            .matching_status()
+            .await
+            .map(|s| s.matching())
+            .unwrap_or(false);
+        if matching != Some(status) {
+            if status {
+                println!("{}: {}", "Matching".bold(), "queryables reachable".green());
+            } else {
+                println!("{}: {}", "Matching".bold(), "no queryable reachable".red());
+            }
+            matching = Some(status);
+        }
+
+        let mut get = querier
+            .get()
+            .parameters(parameters.to_string())
+            .attachment(p.attachment.clone());
+        if let Some(ref body) = p.body {
+            get = get.payload(body.clone());
+        }
+        let replies = get.await.unwrap();
+        let mut current = BTreeMap::<String, String>::new();
+        while let Ok(reply) = replies.recv_async().await {
+            match reply.result() {
+                Ok(sample) => {
+                    current.insert(sample.key_expr().to_string(), payload_to_string(sample));
+                }
+                Err(e) => println!("\t{}: {}", "Result".bold(), e),
+            }
+        }
+
+        match previous {
+            None => {
+                println!("{}({}): {} keys", "Round".bold(), round, current.len());
+                for (key, value) in &current {
+                    println!("\t{}: {}", key.bold(), value);
+                }
+            }
+            Some(ref previous) => {
+                let mut changes = Vec::new();
+                for (key, value) in &current {
+                    match previous.get(key) {
+                        None => changes.push(format!("\t{} {}: {}", "+".green(), key.bold(), value)),
+                        Some(old) if old != value => changes.push(format!(
+                            "\t{} {}: {} -> {}",
+                            "~".yellow(),
+                            key.bold(),
+                            old,
+                            value
+                        )),
+                        Some(_) => {}
+                    }
+                }
+                for key in previous.keys().filter(|k| !current.contains_key(*k)) {
+                    changes.push(format!("\t{} {}", "-".red(), key.bold()));
+                }
+                if !changes.is_empty() {
+                    println!("{}({}): {} changes", "Round".bold(), round, changes.len());
+                    for change in changes {
+                        println!("{}", change);
+                    }
+                }
+            }
+        }
+        previous = Some(current);
+        tokio::time::sleep(period).await;
+    }
+}
+
+fn print_query_analysis(analysis: &QueryAnalysis, elapsed: Duration) {
+    println!("\n{}", "Query Analysis".bold().underline());
+    println!(
+        "\t{} replies, {} errors in {:?}",
+        analysis.replies.len(),
+        analysis.errors.len(),
+        elapsed
+    );
+
+    println!("{}:", "Repliers".bold());
+    for (replier, replies) in analysis.by_replier() {
+        let first = replies.iter().map(|r| r.latency).min().unwrap_or_default();
+        let last = replies.iter().map(|r| r.latency).max().unwrap_or_default();
+        println!(
+            "\t{}: {} replies, first after {:?}, last after {:?}",
+            replier.bold(),
+            replies.len(),
+            first,
+            last
+        );
+        for r in replies {
+            println!("\t\t- {}", r.key);
+        }
+    }
+    for e in &analysis.errors {
+        println!(
+            "\t{}: error after {:?}: {}",
+            e.replier.bold(),
+            e.latency,
+            e.message.red()
+        );
+    }
+
+    let conflicts = analysis.conflicts();
+    if conflicts.is_empty() {
+        println!("{}: {}", "Conflicts".bold(), "None".green());
+    } else {
+        println!("{}:", "Conflicts".bold());
+        for (key, replies) in conflicts {
+            println!("\t{}:", key.yellow());
+            for r in replies {
                 println!(
-                    "\t{}: {}",
-                    "Value".bold(),
-                    result.payload().try_to_string().unwrap()
+                    "\t\t{}: {} @ {}",
+                    r.replier,
+                    r.value,
+                    r.timestamp
+                        .as_ref()
+                        .map(|ts| ts.to_string())
+                        .unwrap_or_else(|| "None".into())
                 );
             }
-            Err(e) => {
-                println!("\t{}: {}", "Result".bold(), e);
+        }
+    }
+
+    println!("{}:", "Consolidated".bold());
+    for (key, r) in analysis.consolidated() {
+        println!("\t{}: {} (from {})", key.bold(), r.value, r.replier);
+    }
+}
+
+// Returns true when all the replicas agree on the content of the key expression.
+pub async fn do_check_consistency(z: &zenoh::Session, sub_matches: &ArgMatches) -> bool {
+    let kexpr: String = resolve_argument(sub_matches, "KEY_EXPR", false)
+        .await
+        .unwrap();
+    let expected = resolve_optional_argument::<usize>(sub_matches, "replicas", false)
+        .await
+        .expect("The number of replicas should be an integer");
+
+    let p = QueryParams {
+        query_expr: kexpr.clone(),
+        target: QueryTarget::AllComplete,
+        consolidation: ConsolidationMode::None,
+        ..Default::default()
+    };
+    let start = Instant::now();
+    let replies = do_get(z, &p).await.unwrap();
+    let mut analysis = QueryAnalysis::new();
+    while let Ok(reply) = replies.recv_async().await {
+        let replier = reply
+            .replier_id()
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| "Unknown".into());
+        match reply.result() {
+            Ok(sample) => analysis.record(ReplyRecord {
+                replier,
+                key: sample.key_expr().to_string(),
+                value: payload_to_string(sample),
+                timestamp: sample.timestamp().cloned(),
+                latency: start.elapsed(),
+            }),
+            Err(e) => analysis.record_error(ReplyErrorRecord {
+                replier,
+                message: e.to_string(),
+                latency: start.elapsed(),
+            }),
+        }
+    }
+
+    let report = analysis.consistency();
+    println!(
+        "{}: {} ({} replicas, {} keys)",
+        "Consistency Check".bold(),
+        kexpr,
+        report.replicas.len(),
+        report.keys
+    );
+    for replica in &report.replicas {
+        println!("\t- {}", replica);
+    }
+    let mut consistent = report.is_consistent() && analysis.errors.is_empty();
+    // With fewer than two replicas, there is nothing to compare.
+    match expected {
+        Some(expected) if report.replicas.len() != expected => {
+            println!(
+                "{}: expected {} replicas, {} replied",
+                "Replicas".bold().red(),
+                expected,
+                report.replicas.len()
+            );
+            consistent = false;
+        }
+        None if report.replicas.len() < MIN_CONSISTENCY_REPLICAS => {
+            println!(
+                "{}: at least {} replicas are needed to compare, {} replied",
+                "Replicas".bold().red(),
+                MIN_CONSISTENCY_REPLICAS,
+                report.replicas.len()
+            );
+            consistent = false;
+        }
+        _ => {}
+    }
+    for e in &analysis.errors {
+        println!("{}: {}: {}", "Error".bold().red(), e.replier, e.message);
+    }
+    for m in &report.missing {
+        println!("{}: {} on {}", "Missing".bold().red(), m.key, m.replier);
+    }
+    let ts = |ts: &Option<Timestamp>| {
+        ts.as_ref()
+            .map(|ts| ts.to_string())
+            .unwrap_or_else(|| "None".into())
+    };
+    for s in &report.stale {
+        println!(
+            "{}: {} on {} ({} < {})",
+            "Stale".bold().yellow(),
+            s.key,
+            s.replier,
+            ts(&s.timestamp),
+            ts(&s.latest)
+        );
+    }
+    for c in &report.conflicts {
+        println!("{}: {} @ {}", "Conflict".bold().red(), c.key, ts(&c.timestamp));
+        for (replier, value) in &c.values {
+            println!("\t{}: {}", replier, value);
+        }
+    }
+    if consistent {
+        println!("{}", "Replicas are aligned".bold().green());
+    }
+    consistent
+}
+
+pub async fn do_export(z: &zenoh::Session, sub_matches: &ArgMatches) {
+    let kexpr: String = resolve_argument(sub_matches, "KEY_EXPR", false)
+        .await
+        .unwrap();
+    let target: String = resolve_argument(sub_matches, "TARGET", false)
+        .await
+        .unwrap();
+
+    let entries = do_fetch_entries(z, &kexpr).await;
+    write_snapshot(Path::new(&target), &entries).expect("Unable to write the snapshot");
+    println!("Exported {} keys to {}", entries.len(), target.bold());
+}
+
+pub async fn do_import(z: &zenoh::Session, sub_matches: &ArgMatches) {
+    let source: String = resolve_argument(sub_matches, "SOURCE", false)
+        .await
+        .unwrap();
+    let from = sub_matches.get_one::<String>("from").map(|s| s.as_str());
+    let to = sub_matches.get_one::<String>("to").map(|s| s.as_str());
+    let keep_timestamps = resolve_bool_argument(sub_matches, "keep-timestamps");
+
+    let entries = read_snapshot(Path::new(&source)).expect("Unable to read the snapshot");
+    for entry in &entries {
+        let key = rewrite_prefix(&entry.key, from, to);
+        match do_put_entry(z, &key, entry, keep_timestamps).await {
+            Ok(()) => println!("\t{}", key),
+            Err(e) => println!("\t{}: {}", key.red(), e),
+        }
+    }
+    println!("Imported {} keys from {}", entries.len(), source.bold());
+}
+
+// Copies the key space between two sessions, usually opened on distinct networks.
+pub async fn do_cp(src: &zenoh::Session, dst: &zenoh::Session, sub_matches: &ArgMatches) {
+    let kexpr: String = resolve_argument(sub_matches, "KEY_EXPR", false)
+        .await
+        .unwrap();
+    let from = sub_matches.get_one::<String>("from").map(|s| s.as_str());
+    let to = sub_matches.get_one::<String>("to").map(|s| s.as_str());
+    let dry_run = resolve_bool_argument(sub_matches, "dry-run");
+
+    let entries = do_fetch_entries(src, &kexpr).await;
+    for entry in &entries {
+        let key = rewrite_prefix(&entry.key, from, to);
+        if dry_run {
+            println!(
+                "\t{} -> {} ({} bytes, {})",
+                entry.key,
+                key.bold(),
+                entry.payload.len(),
+                entry.encoding
+            );
+        } else {
+            match do_put_entry(dst, &key, entry, true).await {
+                Ok(()) => println!("\t{} -> {}", entry.key, key.bold()),
+                Err(e) => println!("\t{} -> {}: {}", entry.key, key.red(), e),
             }
         }
     }
+    if dry_run {
+        println!("{} keys would be copied", entries.len());
+    } else {
+        println!("Copied {} keys", entries.len());
+    }
+}
+
+// Retrieves everything stored under the key expression, keeping the
+// encoding, timestamp and attachment of each sample.
+async fn do_fetch_entries(z: &zenoh::Session, kexpr: &str) -> Vec<SnapshotEntry> {
+    let p = QueryParams {
+        query_expr: kexpr.to_string(),
+        target: QueryTarget::All,
+        consolidation: ConsolidationMode::Latest,
+        ..Default::default()
+    };
+    let replies = do_get(z, &p).await.unwrap();
+    let mut entries = Vec::new();
+    while let Ok(reply) = replies.recv_async().await {
+        match reply.result() {
+            Ok(sample) => entries.push(SnapshotEntry {
+                key: sample.key_expr().to_string(),
+                payload: sample.payload().to_bytes().to_vec(),
+                encoding: sample.encoding().to_string(),
+                timestamp: sample.timestamp().map(|ts| ts.to_string()),
+                attachment: sample.attachment().map(|a| a.to_bytes().to_vec()),
+            }),
+            Err(e) => println!("\t{}: {}", "Result".bold(), e),
+        }
+    }
+    entries
+}
+
+// Publishes an entry with its original timestamp, or with a new one so that
+// storages holding a more recent value or a tombstone do not drop it.
+async fn do_put_entry(
+    z: &zenoh::Session,
+    key: &str,
+    entry: &SnapshotEntry,
+    keep_timestamp: bool,
+) -> ZResult<()> {
+    let timestamp = if keep_timestamp {
+        entry
+            .timestamp
+            .as_deref()
+            .and_then(|ts| ts.parse::<Timestamp>().ok())
+    } else {
+        Some(z.new_timestamp())
+    };
+    z.put(key, entry.payload.clone())
+        .encoding(Encoding::from(entry.encoding.as_str()))
+        .attachment(entry.attachment.clone())
+        .timestamp(timestamp)
+        .await
+}
+
+pub async fn do_tree(z: &zenoh::Session, sub_matches: &ArgMatches) {
+    let prefix: String = resolve_argument(sub_matches, "PREFIX", false)
+        .await
+        .unwrap();
+    let snapshot = resolve_bool_argument(sub_matches, "snapshot");
+    let refresh = *resolve_optional_argument::<u64>(sub_matches, "refresh", false)
+        .await
+        .unwrap()
+        .get_or_insert(2);
+    let kexpr = format!("{}/**", prefix.trim_end_matches('/'));
+
+    // As for subscribe, the subscriber goes first to avoid missing updates
+    // that happen while the storages and queryables are being queried.
+    let sub = if snapshot {
+        None
+    } else {
+        Some(z.declare_subscriber(&kexpr).await.unwrap())
+    };
+
+    let mut tree = KeyTree::new();
+    let p = QueryParams {
+        query_expr: kexpr.clone(),
+        target: QueryTarget::All,
+        consolidation: ConsolidationMode::Latest,
+        ..Default::default()
+    };
+    let replies = do_get(z, &p).await.unwrap();
+    while let Ok(reply) = replies.recv_async().await {
+        if let Ok(sample) = reply.result() {
+            tree.update(sample.key_expr().as_str(), payload_to_string(sample));
+        }
+    }
+
+    let Some(sub) = sub else {
+        print!("{}", tree.render());
+        return;
+    };
+
+    let mut ticker = tokio::time::interval(Duration::from_secs(refresh));
+    loop {
+        tokio::select! {
+            result = sub.recv_async() => {
+                match result {
+                    Ok(sample) => match sample.kind() {
+                        SampleKind::Put => {
+                            tree.update(sample.key_expr().as_str(), payload_to_string(&sample))
+                        }
+                        SampleKind::Delete => tree.remove(sample.key_expr().as_str()),
+                    },
+                    Err(_) => break,
+                }
+            }
+            _ = ticker.tick() => {
+                // Clear the screen and move the cursor back to the top
+                print!("\x1B[2J\x1B[H");
+                println!("{}: {} ({} keys)\n", "Key Space".bold(), kexpr, tree.len());
+                print!("{}", tree.render());
+            }
+        }
+    }
+}
+
+fn payload_to_string(sample: &Sample) -> String {
+    bytes_to_string(sample.payload())
+}
+
+fn bytes_to_string(payload: &ZBytes) -> String {
+    payload
+        .try_to_string()
+        .map(|s| s.to_string())
+        .unwrap_or_else(|_| "[binary]".into())
 }
 
 #[cfg(feature = "video")]
@@ -386,25 +1230,74 @@ pub async fn do_stream(z: &zenoh::Session, sub_matches: &ArgMatches) {
 }
 
 pub async fn do_queryable(z: &zenoh::Session, sub_matches: &ArgMatches) {
+    let defaults = QueryableParams::default();
     let complete = resolve_bool_argument(sub_matches, "complete");
     let exec_script = resolve_bool_argument(sub_matches, "script");
-    let site_packages = if let Some(path) =
-        resolve_optional_argument::<String>(sub_matches, "packages", false)
-            .await
-            .unwrap()
-    {
-        format!("import sys\nsys.path.append('{}')\n", path)
-    } else {
-        String::default()
-    };
+    let packages = resolve_optional_argument::<String>(sub_matches, "packages", false)
+        .await
+        .unwrap();
+    let workers = resolve_optional_argument::<usize>(sub_matches, "workers", false)
+        .await
+        .expect("The number of workers should be an integer")
+        .unwrap_or(defaults.workers);
+    let timeout = resolve_optional_argument::<u64>(sub_matches, "timeout", false)
+        .await
+        .expect("The timeout should be an integer")
+        .map(Duration::from_millis)
+        .unwrap_or(defaults.timeout);
+    let delay = resolve_optional_argument::<String>(sub_matches, "delay", false)
+        .await
+        .unwrap()
+        .map(|d| d.parse::<Delay>().unwrap_or_else(|e| panic!("{}", e)));
+    let error_rate = resolve_optional_argument::<String>(sub_matches, "error-rate", false)
+        .await
+        .unwrap()
+        .map(|r| parse_rate(&r).unwrap_or_else(|e| panic!("{}", e)))
+        .unwrap_or(defaults.error_rate);
+    let drop_rate = resolve_optional_argument::<String>(sub_matches, "drop-rate", false)
+        .await
+        .unwrap()
+        .map(|r| parse_rate(&r).unwrap_or_else(|e| panic!("{}", e)))
+        .unwrap_or(defaults.drop_rate);
 
+    let reply_error = resolve_bool_argument(sub_matches, "error");
     let file_based = resolve_bool_argument(sub_matches, "file");
     let kexpr: String = resolve_argument(sub_matches, "KEY_EXPR", false)
         .await
         .unwrap();
-    let reply: String = resolve_argument(sub_matches, "REPLY", file_based)
+    let exec = resolve_optional_argument::<String>(sub_matches, "exec", false)
         .await
         .unwrap();
+    let mut reply: String = resolve_optional_argument(sub_matches, "REPLY", file_based)
+        .await
+        .unwrap()
+        .unwrap_or_default();
+    // With a data file, each key of the file matching a query gets its own
+    // reply.
+    let data_path = sub_matches.get_one::<String>("data");
+    let mut data = resolve_optional_argument::<String>(sub_matches, "data", true)
+        .await
+        .unwrap()
+        .map(|content| parse_data(&content).unwrap_or_else(|e| panic!("Invalid data file: {}", e)));
+
+    let runtime = if let Some(ref cmd) = exec {
+        Some(QueryBackend::Command(CommandRuntime::new(cmd, workers, timeout)))
+    } else if exec_script {
+        let path = file_based
+            .then(|| sub_matches.get_one::<String>("REPLY"))
+            .flatten();
+        let engine = ScriptEngine::detect(
+            sub_matches.get_one::<String>("engine").map(String::as_str),
+            path.map(String::as_str),
+        )
+        .unwrap_or_else(|e| panic!("{}", e));
+        Some(QueryBackend::Script(
+            ScriptRuntime::new(z, engine, &reply, packages.as_deref(), workers, timeout)
+                .unwrap_or_else(|e| panic!("{}", e)),
+        ))
+    } else {
+        None
+    };
 
     let queryable = z
         .declare_queryable(kexpr.clone())
@@ -412,53 +1305,468 @@ pub async fn do_queryable(z: &zenoh::Session, sub_matches: &ArgMatches) {
         .await
         .expect("Unable to declare queryable");
 
+    // The script, reply or data file is reloaded when modified, without
+    // redeclaring the queryable.
+    let mut watch = if let Some(path) = data_path {
+        Some(FileWatch::new(path))
+    } else if file_based {
+        sub_matches.get_one::<String>("REPLY").map(FileWatch::new)
+    } else {
+        None
+    };
+    let mut reload = tokio::time::interval(Duration::from_millis(QUERYABLE_RELOAD_PERIOD_MS));
+
     let mut n = 0;
     let si = SourceInfo::new(Some(queryable.id()), Some(0));
     println!("\tQueryable Running!");
-    use pyo3::prelude::*;
-    use pyo3::types::PyDict;
-    use std::ffi::CString;
-    while let Ok(query) = queryable.recv_async().await {
+    loop {
+        let query = tokio::select! {
+            query = queryable.recv_async() => match query {
+                Ok(query) => query,
+                Err(_) => break,
+            },
+            _ = reload.tick(), if watch.is_some() => {
+                let watch = watch.as_mut().unwrap();
+                if watch.changed() {
+                    if let Some(ref mut data) = data {
+                        reload_data(&watch.path, data).await;
+                        continue;
+                    }
+                    let script = match runtime {
+                        Some(QueryBackend::Script(ref script)) => Some(script),
+                        _ => None,
+                    };
+                    reload_reply(&watch.path, &mut reply, script).await;
+                }
+                continue;
+            }
+        };
         n += 1;
         println!("{}({}):", "Query".bold(), n);
         println!("\t{}: {}", "Key Expr".bold(), query.key_expr());
-        if exec_script {
-            let result = Python::attach(|py| {
-                let locals = PyDict::new(py);
-                let key_expr = query.key_expr().to_string();
-                let payload = query
-                    .payload()
-                    .map(|p| p.to_bytes().to_vec())
-                    .unwrap_or_else(Vec::new);
-                locals.set_item("key_expr", key_expr).unwrap();
-                locals.set_item("payload", payload).unwrap();
-
-                let script = CString::new(site_packages.clone() + reply.as_str()).unwrap();
-                py.run(script.as_c_str(), None, Some(&locals)).unwrap();
-                locals
-                    .get_item("result")
-                    .unwrap()
-                    .unwrap()
-                    .extract::<String>()
-                    .unwrap()
-            });
-
-            query
-                .reply(query.key_expr(), &result)
-                .source_info(si.clone())
-                .timestamp(z.new_timestamp())
-                .await
-                .unwrap();
+        if happens(drop_rate) {
+            println!("\t{}", "Dropped".bold().yellow());
+            continue;
+        }
+        let inject_error = happens(error_rate);
+        if inject_error {
+            println!("\t{}", "Injected Error".bold().yellow());
+        }
+        let delay = delay.as_ref().map(Delay::pick);
+        if let Some(delay) = delay {
+            println!("\t{}: {:?}", "Delay".bold(), delay);
+        }
+        let entries = data.as_ref().map(|data| matching_entries(data, &query));
+        if !inject_error {
+            for (key, _, _) in entries.iter().flatten() {
+                println!("\t{}: {}", "Reply".bold(), key);
+            }
+        }
+
+        // Replies are sent on their own, so that delays, scripts and commands
+        // do not hold back the next queries.
+        let (runtime, reply, si, ts) =
+            (runtime.clone(), reply.clone(), si.clone(), z.new_timestamp());
+        tokio::spawn(async move {
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+            let result = if inject_error {
+                query.reply_err(INJECTED_ERROR).await
+            } else if let Some(entries) = entries {
+                reply_entries(&query, entries, &si, ts).await
+            } else if let Some(runtime) = runtime {
+                match runtime.run(script_input(&query)).await {
+                    Ok(output) => reply_script_output(&query, output, reply_error, &si, ts).await,
+                    Err(e) => {
+                        println!("{}({}): {}", "Backend Error".bold().red(), n, e);
+                        query.reply_err(e).await
+                    }
+                }
+            } else if reply_error {
+                query.reply_err(&reply).await
+            } else {
+                query
+                    .reply(query.key_expr(), &reply)
+                    .source_info(si)
+                    .timestamp(ts)
+                    .await
+            };
+            if let Err(e) = result {
+                println!("{}({}): {}", "Reply Error".bold().red(), n, e);
+            }
+        });
+    }
+}
+
+// The programmable backends answering queries.
+#[derive(Clone)]
+enum QueryBackend {
+    Script(ScriptRuntime),
+    Command(CommandRuntime),
+}
+
+impl QueryBackend {
+    async fn run(&self, input: ScriptInput) -> Result<ScriptOutput, String> {
+        match self {
+            QueryBackend::Script(script) => script.run(input).await,
+            QueryBackend::Command(command) => command.run(input).await,
+        }
+    }
+}
+
+// Polls the modification time of a file.
+struct FileWatch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl FileWatch {
+    fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let modified = Self::modified(&path);
+        Self { path, modified }
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    fn changed(&mut self) -> bool {
+        let modified = Self::modified(&self.path);
+        if modified.is_some() && modified != self.modified {
+            self.modified = modified;
+            true
         } else {
-            query
-                .reply(query.key_expr(), &reply)
-                .source_info(si.clone())
-                .timestamp(z.new_timestamp())
+            false
+        }
+    }
+}
+
+async fn reload_reply(path: &Path, reply: &mut String, runtime: Option<&ScriptRuntime>) {
+    let content = match tokio::fs::read_to_string(path).await {
+        Ok(content) => content,
+        Err(e) => {
+            println!("{}: {}: {}", "Reload Error".bold().red(), path.display(), e);
+            return;
+        }
+    };
+    let reloaded = match runtime {
+        Some(runtime) => runtime.reload(&content),
+        None => Ok(()),
+    };
+    match reloaded {
+        Ok(()) => {
+            *reply = content;
+            println!("{}: {}", "Reloaded".bold().green(), path.display());
+        }
+        Err(e) => println!(
+            "{}: {}, still serving the previous version\n{}",
+            "Reload Error".bold().red(),
+            path.display(),
+            e
+        ),
+    }
+}
+
+async fn reload_data(path: &Path, data: &mut BTreeMap<String, (String, &'static str)>) {
+    let parsed = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|content| parse_data(&content));
+    match parsed {
+        Ok(parsed) => {
+            *data = parsed;
+            println!("{}: {}", "Reloaded".bold().green(), path.display());
+        }
+        Err(e) => println!(
+            "{}: {}, still serving the previous version\n{}",
+            "Reload Error".bold().red(),
+            path.display(),
+            e
+        ),
+    }
+}
+
+// The entries of a data file whose key intersects the query key expression.
+fn matching_entries(
+    data: &BTreeMap<String, (String, &'static str)>,
+    query: &Query,
+) -> Vec<(String, String, &'static str)> {
+    data.iter()
+        .filter(|(key, _)| {
+            zenoh::key_expr::keyexpr::new(key.as_str())
+                .is_ok_and(|ke| query.key_expr().intersects(ke))
+        })
+        .map(|(key, (value, encoding))| (key.clone(), value.clone(), *encoding))
+        .collect()
+}
+
+async fn reply_entries(
+    query: &Query,
+    entries: Vec<(String, String, &'static str)>,
+    si: &SourceInfo,
+    ts: Timestamp,
+) -> ZResult<()> {
+    for (key, value, encoding) in entries {
+        query
+            .reply(key, value)
+            .encoding(encoding)
+            .source_info(si.clone())
+            .timestamp(ts)
+            .await?;
+    }
+    Ok(())
+}
+
+fn script_input(query: &Query) -> ScriptInput {
+    ScriptInput {
+        key_expr: query.key_expr().to_string(),
+        parameters: query
+            .parameters()
+            .iter()
+            .map(|(name, value)| (unescape(name), unescape(value)))
+            .collect(),
+        payload: query
+            .payload()
+            .map(|p| p.to_bytes().to_vec())
+            .unwrap_or_default(),
+        encoding: query.encoding().map(|e| e.to_string()),
+        attachment: query.attachment().map(|a| a.to_bytes().to_vec()),
+        sample: None,
+    }
+}
+
+async fn reply_script_output(
+    query: &Query,
+    output: ScriptOutput,
+    reply_error: bool,
+    si: &SourceInfo,
+    ts: Timestamp,
+) -> ZResult<()> {
+    if let Some(error) = output.error {
+        return query.reply_err(error).await;
+    }
+    let encoding = output.encoding.map(Encoding::from).unwrap_or_default();
+    for (key, payload) in output.replies {
+        if reply_error {
+            query.reply_err(payload).encoding(encoding.clone()).await?;
+            continue;
+        }
+        let key = key.unwrap_or_else(|| query.key_expr().to_string());
+        query
+            .reply(key, payload)
+            .encoding(encoding.clone())
+            .attachment(output.attachment.clone())
+            .source_info(si.clone())
+            .timestamp(ts)
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn do_mock(z: &zenoh::Session, sub_matches: &ArgMatches) {
+    let complete = resolve_bool_argument(sub_matches, "complete");
+    let content: String = resolve_argument(sub_matches, "RULES", true).await.unwrap();
+    let rules = parse_rules(&content).unwrap_or_else(|e| panic!("Invalid mock rules: {}", e));
+
+    let n = Arc::new(AtomicU64::new(0));
+    let rules = Arc::new(rules);
+    let mut tasks = tokio::task::JoinSet::new();
+    for (i, rule) in rules.iter().enumerate() {
+        let queryable = z
+            .declare_queryable(rule.key_expr.clone())
+            .complete(complete)
+            .await
+            .unwrap_or_else(|e| panic!("Unable to declare queryable for {}: {}", rule.name, e));
+        println!("\t{}: {} on {}", "Rule".bold(), rule.name, rule.key_expr);
+        // The earlier rules that may also receive the queries of this one.
+        let earlier: Vec<usize> = (0..i)
+            .filter(|&j| key_exprs_intersect(&rules[j].key_expr, &rule.key_expr))
+            .collect();
+        let (z, n, rules) = (z.clone(), n.clone(), rules.clone());
+        tasks.spawn(async move {
+            let rule = &rules[i];
+            let si = SourceInfo::new(Some(queryable.id()), Some(0));
+            while let Ok(query) = queryable.recv_async().await {
+                let input = script_input(&query);
+                let payload = String::from_utf8_lossy(&input.payload).to_string();
+                // Queries not matching the parameters or payload of the rule
+                // are left to the other rules, while the ones matching an
+                // earlier rule are answered by that rule only.
+                let Some(captures) = rule.matches(&input.key_expr, &input.parameters, &payload)
+                else {
+                    continue;
+                };
+                let taken = earlier.iter().any(|&j| {
+                    rules[j]
+                        .matches(&input.key_expr, &input.parameters, &payload)
+                        .is_some()
+                });
+                if taken {
+                    continue;
+                }
+                let n = n.fetch_add(1, Ordering::Relaxed) + 1;
+                println!("{}({}):", "Query".bold(), n);
+                println!("\t{}: {}", "Key Expr".bold(), query.key_expr());
+                println!("\t{}: {}", "Rule".bold(), rule.name);
+                if happens(rule.drop_rate) {
+                    println!("\t{}", "Dropped".bold().yellow());
+                    continue;
+                }
+
+                let (rules, si, ts) = (rules.clone(), si.clone(), z.new_timestamp());
+                tokio::spawn(async move {
+                    let rule = &rules[i];
+                    if let Some(ref latency) = rule.latency {
+                        tokio::time::sleep(latency.pick()).await;
+                    }
+                    let fill = |template: &str| {
+                        render(template, &captures, &input.key_expr, &input.parameters, &payload)
+                    };
+                    let output = match rule.error {
+                        Some(ref error) if happens(rule.error_rate) => ScriptOutput {
+                            error: Some(fill(error).into_bytes()),
+                            ..Default::default()
+                        },
+                        _ => ScriptOutput {
+                            replies: vec![(None, fill(&rule.reply).into_bytes())],
+                            encoding: rule.encoding.clone(),
+                            ..Default::default()
+                        },
+                    };
+                    if let Err(e) = reply_script_output(&query, output, false, &si, ts).await {
+                        println!("{}({}): {}", "Reply Error".bold().red(), n, e);
+                    }
+                });
+            }
+        });
+    }
+    println!("\tMock Running!");
+    while tasks.join_next().await.is_some() {}
+}
+
+fn key_exprs_intersect(a: &str, b: &str) -> bool {
+    use zenoh::key_expr::keyexpr;
+    match (keyexpr::new(a), keyexpr::new(b)) {
+        (Ok(a), Ok(b)) => a.intersects(b),
+        _ => false,
+    }
+}
+
+pub async fn do_serve_dir(z: &zenoh::Session, sub_matches: &ArgMatches) {
+    let complete = resolve_bool_argument(sub_matches, "complete");
+    let write = resolve_bool_argument(sub_matches, "write");
+    let prefix: String = resolve_argument(sub_matches, "PREFIX", false).await.unwrap();
+    let prefix = prefix.trim_end_matches('/').to_string();
+    let dir: String = resolve_argument(sub_matches, "DIR", false).await.unwrap();
+    let root = Path::new(&dir);
+    if !root.is_dir() {
+        panic!("{} is not a directory", dir);
+    }
+
+    let kexpr = join_key(&prefix, "**");
+    let queryable = z
+        .declare_queryable(kexpr.clone())
+        .complete(complete)
+        .await
+        .expect("Unable to declare queryable");
+    // Puts are written back to the directory only when asked for.
+    let subscriber = if write {
+        Some(
+            z.declare_subscriber(kexpr.clone())
                 .await
-                .unwrap();
+                .expect("Unable to declare subscriber"),
+        )
+    } else {
+        None
+    };
+    let next_sample = || async {
+        match subscriber {
+            Some(ref sub) => sub.recv_async().await,
+            None => std::future::pending().await,
+        }
+    };
+
+    let mut n = 0;
+    let si = SourceInfo::new(Some(queryable.id()), Some(0));
+    println!("\tServing {} on {}", dir, kexpr);
+    loop {
+        tokio::select! {
+            query = queryable.recv_async() => {
+                let Ok(query) = query else { break };
+                n += 1;
+                println!("{}({}):", "Query".bold(), n);
+                println!("\t{}: {}", "Key Expr".bold(), query.key_expr());
+                // Walking the directory blocks, keep it off the executor
+                let dir = root.to_path_buf();
+                let listing = tokio::task::spawn_blocking(move || list_files(&dir))
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|files| files.map_err(|e| e.to_string()));
+                let files = match listing {
+                    Ok(files) => files,
+                    Err(e) => {
+                        println!("\t{}: {}", "Error".bold().red(), e);
+                        let _ = query.reply_err(e).await;
+                        continue;
+                    }
+                };
+                for (key, path) in files {
+                    let key = join_key(&prefix, &key);
+                    let matches = zenoh::key_expr::keyexpr::new(&key)
+                        .is_ok_and(|ke| query.key_expr().intersects(ke));
+                    if !matches {
+                        continue;
+                    }
+                    let content = match tokio::fs::read(&path).await {
+                        Ok(content) => content,
+                        Err(e) => {
+                            println!("\t{}: {}: {}", "Error".bold().red(), path.display(), e);
+                            continue;
+                        }
+                    };
+                    println!("\t{}: {}", "Reply".bold(), key);
+                    let result = query
+                        .reply(key, content)
+                        .encoding(encoding_for(&path))
+                        .source_info(si.clone())
+                        .timestamp(z.new_timestamp())
+                        .await;
+                    if let Err(e) = result {
+                        println!("\t{}: {}", "Reply Error".bold().red(), e);
+                    }
+                }
+            }
+            sample = next_sample() => {
+                let Ok(sample) = sample else { break };
+                let key = sample.key_expr().as_str();
+                let relative = rewrite_prefix(key, Some(&prefix), None);
+                let path = match key_dir(root, &relative) {
+                    Ok(path) => path,
+                    Err(e) => {
+                        println!("{}: {}", "Write Error".bold().red(), e);
+                        continue;
+                    }
+                };
+                let result = match sample.kind() {
+                    SampleKind::Put => {
+                        let dir = path.parent().unwrap_or(root);
+                        tokio::fs::create_dir_all(dir)
+                            .await
+                            .and(tokio::fs::write(&path, sample.payload().to_bytes()).await)
+                    }
+                    SampleKind::Delete => tokio::fs::remove_file(&path).await,
+                };
+                match result {
+                    Ok(()) => println!("{} {} -> {}", sample.kind(), key, path.display()),
+                    Err(e) => println!("{}: {}: {}", "Write Error".bold().red(), path.display(), e),
+                }
+            }
         }
     }
 }
+
 pub async fn do_declare_liveliness_token(
     z: &zenoh::Session,
     key_expr: &str,
@@ -528,10 +1836,17 @@ pub async fn do_publish_with(z: &zenoh::Session, p: &PublishParams) -> Vec<Zenoh
                 .await
                 .unwrap();
         }
+        let meta = SampleMetadata {
+            encoding: Encoding::ZENOH_STRING.to_string(),
+            priority: p.priority.unwrap_or(Priority::Data),
+            size: value.len(),
+            ..Default::default()
+        };
         events.push(ZenohEvent::Sample {
             key: p.key_expr.clone(),
             value,
             attachment: p.attachment.clone(),
+            meta,
             n: i as u64,
         });
         if p.period_ms != 0 {
@@ -545,38 +1860,28 @@ pub async fn do_delete_with(z: &zenoh::Session, p: &DeleteParams) {
     z.delete(&p.key_expr).await.unwrap();
 }
 
+// Issues the query described by `p`, shared by the query flavours that need
+// to process the replies themselves.
+pub async fn do_get(
+    z: &zenoh::Session,
+    p: &QueryParams,
+) -> ZResult<FifoChannelHandler<Reply>> {
+    let mut get = z
+        .get(p.selector())
+        .target(p.target)
+        .consolidation(p.consolidation)
+        .attachment(p.attachment.clone());
+    if let Some(ref body) = p.body {
+        get = get.payload(body.clone());
+    }
+    if let Some(timeout) = p.timeout {
+        get = get.timeout(timeout);
+    }
+    get.await
+}
+
 pub async fn do_query_with(z: &zenoh::Session, p: &QueryParams) -> Vec<ZenohEvent> {
-    let replies = if let Some(ref body) = p.body {
-        if let Some(ref attach) = p.attachment {
-            z.get(&p.query_expr)
-                .target(p.target)
-                .consolidation(p.consolidation)
-                .payload(body.clone())
-                .attachment(attach.clone())
-                .await
-                .unwrap()
-        } else {
-            z.get(&p.query_expr)
-                .target(p.target)
-                .consolidation(p.consolidation)
-                .payload(body.clone())
-                .await
-                .unwrap()
-        }
-    } else if let Some(ref attach) = p.attachment {
-        z.get(&p.query_expr)
-            .target(p.target)
-            .consolidation(p.consolidation)
-            .attachment(attach.clone())
-            .await
-            .unwrap()
-    } else {
-        z.get(&p.query_expr)
-            .target(p.target)
-            .consolidation(p.consolidation)
-            .await
-            .unwrap()
-    };
+    let replies = do_get(z, p).await.unwrap();
 
     let mut events = Vec::new();
     let mut n: u64 = 0;
@@ -594,11 +1899,17 @@ pub async fn do_query_with(z: &zenoh::Session, p: &QueryParams) -> Vec<ZenohEven
                     key,
                     value,
                     attachment: None,
+                    meta: SampleMetadata::from(sample),
                     n,
                 });
             }
             Err(e) => {
-                events.push(ZenohEvent::Error(e.to_string()));
+                events.push(ZenohEvent::ReplyError {
+                    replier: reply.replier_id().map(|id| id.to_string()),
+                    value: bytes_to_string(e.payload()),
+                    encoding: e.encoding().to_string(),
+                    n,
+                });
             }
         }
     }
@@ -688,7 +1999,8 @@ pub async fn do_subscribe_streaming(
                         let attachment = sample.attachment()
                             .and_then(|a| a.try_to_string().ok())
                             .map(|s| s.to_string());
-                        if tx.send(ZenohEvent::Sample { key, value, attachment, n }).await.is_err() {
+                        let meta = SampleMetadata::from(&sample);
+                        if tx.send(ZenohEvent::Sample { key, value, attachment, meta, n }).await.is_err() {
                             break;
                         }
                     }
@@ -700,6 +2012,59 @@ pub async fn do_subscribe_streaming(
     }
 }
 
+#[cfg(feature = "gui")]
+pub async fn do_query_streaming(
+    z: &zenoh::Session,
+    params: QueryParams,
+    tx: tokio::sync::mpsc::Sender<ZenohEvent>,
+    mut cancel: tokio::sync::oneshot::Receiver<()>,
+) {
+    let replies = match do_get(z, &params).await {
+        Ok(r) => r,
+        Err(e) => {
+            let _ = tx.send(ZenohEvent::Error(e.to_string())).await;
+            return;
+        }
+    };
+    let mut n: u64 = 0;
+    loop {
+        tokio::select! {
+            result = replies.recv_async() => {
+                let event = match result {
+                    Ok(reply) => {
+                        n += 1;
+                        match reply.result() {
+                            Ok(sample) => ZenohEvent::Sample {
+                                key: sample.key_expr().to_string(),
+                                value: sample.payload().try_to_string()
+                                    .map(|s| s.to_string())
+                                    .unwrap_or_else(|_| "[binary]".into()),
+                                attachment: sample.attachment()
+                                    .and_then(|a| a.try_to_string().ok())
+                                    .map(|s| s.to_string()),
+                                meta: SampleMetadata::from(sample),
+                                n,
+                            },
+                            Err(e) => ZenohEvent::ReplyError {
+                                replier: reply.replier_id().map(|id| id.to_string()),
+                                value: bytes_to_string(e.payload()),
+                                encoding: e.encoding().to_string(),
+                                n,
+                            },
+                        }
+                    }
+                    Err(_) => break,
+                };
+                if tx.send(event).await.is_err() {
+                    return;
+                }
+            }
+            _ = &mut cancel => break,
+        }
+    }
+    let _ = tx.send(ZenohEvent::Done).await;
+}
+
 #[cfg(feature = "gui")]
 pub async fn do_subscribe_liveliness_streaming(
     z: &zenoh::Session,
@@ -743,9 +2108,29 @@ pub async fn do_queryable_streaming(
     tx: tokio::sync::mpsc::Sender<ZenohEvent>,
     mut cancel: tokio::sync::oneshot::Receiver<()>,
 ) {
-    let site_packages = params.packages_path.as_ref().map(|p| {
-        format!("import sys\nsys.path.append('{}')\n", p)
-    }).unwrap_or_default();
+    let runtime = if let Some(ref cmd) = params.exec {
+        Some(QueryBackend::Command(CommandRuntime::new(cmd, params.workers, params.timeout)))
+    } else if params.exec_script {
+        let engine = ScriptEngine::detect(params.engine.as_deref(), None);
+        match engine.and_then(|engine| {
+            ScriptRuntime::new(
+                z,
+                engine,
+                &params.reply,
+                params.packages_path.as_deref(),
+                params.workers,
+                params.timeout,
+            )
+        }) {
+            Ok(runtime) => Some(QueryBackend::Script(runtime)),
+            Err(e) => {
+                let _ = tx.send(ZenohEvent::Error(e)).await;
+                return;
+            }
+        }
+    } else {
+        None
+    };
 
     let queryable = match z.declare_queryable(&params.key_expr).complete(params.complete).await {
         Ok(q) => q,
@@ -763,36 +2148,48 @@ pub async fn do_queryable_streaming(
                     Ok(query) => {
                         n += 1;
                         let key = query.key_expr().to_string();
-                        if tx.send(ZenohEvent::QueryIn { key: key.clone(), n }).await.is_err() {
+                        if tx.send(ZenohEvent::QueryIn { key, n }).await.is_err() {
                             break;
                         }
-                        if params.exec_script {
-                            use pyo3::prelude::*;
-                            use pyo3::types::PyDict;
-                            use std::ffi::CString;
-                            let reply_str = params.reply.clone();
-                            let site_pkg = site_packages.clone();
-                            let result = Python::attach(|py| {
-                                let locals = PyDict::new(py);
-                                let payload = query.payload()
-                                    .map(|p| p.to_bytes().to_vec())
-                                    .unwrap_or_default();
-                                locals.set_item("key_expr", key.clone()).unwrap();
-                                locals.set_item("payload", payload).unwrap();
-                                let script = CString::new(site_pkg + reply_str.as_str()).unwrap();
-                                py.run(script.as_c_str(), None, Some(&locals)).unwrap();
-                                locals.get_item("result").unwrap().unwrap().extract::<String>().unwrap()
-                            });
-                            let _ = query.reply(query.key_expr(), &result)
-                                .source_info(si.clone())
-                                .timestamp(z.new_timestamp())
-                                .await;
-                        } else {
-                            let _ = query.reply(query.key_expr(), &params.reply)
-                                .source_info(si.clone())
-                                .timestamp(z.new_timestamp())
-                                .await;
+                        if happens(params.drop_rate) {
+                            continue;
                         }
+                        let inject_error = happens(params.error_rate);
+                        let delay = params.delay.as_ref().map(Delay::pick);
+                        let (runtime, reply, si, ts, tx) = (
+                            runtime.clone(),
+                            params.reply.clone(),
+                            si.clone(),
+                            z.new_timestamp(),
+                            tx.clone(),
+                        );
+                        let reply_error = params.reply_error;
+                        tokio::spawn(async move {
+                            if let Some(delay) = delay {
+                                tokio::time::sleep(delay).await;
+                            }
+                            let result = if inject_error {
+                                query.reply_err(INJECTED_ERROR).await
+                            } else if let Some(runtime) = runtime {
+                                match runtime.run(script_input(&query)).await {
+                                    Ok(output) => reply_script_output(&query, output, reply_error, &si, ts).await,
+                                    Err(e) => {
+                                        let _ = tx.send(ZenohEvent::Error(e.clone())).await;
+                                        query.reply_err(e).await
+                                    }
+                                }
+                            } else if reply_error {
+                                query.reply_err(&reply).await
+                            } else {
+                                query.reply(query.key_expr(), &reply)
+                                    .source_info(si)
+                                    .timestamp(ts)
+                                    .await
+                            };
+                            if let Err(e) = result {
+                                let _ = tx.send(ZenohEvent::Error(e.to_string())).await;
+                            }
+                        });
                     }
                     Err(_) => break,
                 }
@@ -0,0 +1,139 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+// Numeric aggregation of the payloads returned by a query, optionally grouped
+// by one of the chunks of the reply keys.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aggregate {
+    Min,
+    Max,
+    Avg,
+    Sum,
+    Count,
+}
+
+impl FromStr for Aggregate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "min" => Ok(Aggregate::Min),
+            "max" => Ok(Aggregate::Max),
+            "avg" => Ok(Aggregate::Avg),
+            "sum" => Ok(Aggregate::Sum),
+            "count" => Ok(Aggregate::Count),
+            _ => Err(format!(
+                "Unknown aggregate \"{}\", should be one of <min|max|avg|sum|count>",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+            Aggregate::Avg => "avg",
+            Aggregate::Sum => "sum",
+            Aggregate::Count => "count",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Accumulator {
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl Accumulator {
+    pub fn push(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn result(&self, op: Aggregate) -> f64 {
+        match op {
+            Aggregate::Min => self.min,
+            Aggregate::Max => self.max,
+            Aggregate::Avg => self.sum / self.count as f64,
+            Aggregate::Sum => self.sum,
+            Aggregate::Count => self.count as f64,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Aggregation {
+    pub groups: BTreeMap<String, Accumulator>,
+    pub skipped: Vec<String>,
+}
+
+impl Aggregation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the payload of the reply for `key`. Payloads that do not hold a
+    /// number, or whose key has no chunk at the `group_by` position, are
+    /// skipped.
+    pub fn add(&mut self, key: &str, payload: &str, field: Option<&str>, group_by: Option<usize>) {
+        let group = match group_by {
+            Some(i) => match key.split('/').nth(i) {
+                Some(chunk) => chunk.to_string(),
+                None => {
+                    self.skipped.push(key.to_string());
+                    return;
+                }
+            },
+            None => String::new(),
+        };
+        match extract_number(payload, field) {
+            Some(value) => self.groups.entry(group).or_default().push(value),
+            None => self.skipped.push(key.to_string()),
+        }
+    }
+}
+
+/// Reads a number out of a payload, either the payload itself or, when
+/// `field` is given, a field of a JSON object. Nested fields are separated by
+/// dots, e.g. `reading.temperature`. Numbers given as JSON strings are
+/// accepted as well, but not infinite or NaN values.
+pub fn extract_number(payload: &str, field: Option<&str>) -> Option<f64> {
+    let number = match field {
+        None => payload.trim().parse::<f64>().ok(),
+        Some(field) => {
+            let json: Value = serde_json::from_str(payload).ok()?;
+            let value = field
+                .split('.')
+                .try_fold(&json, |v, name| v.get(name))?;
+            match value {
+                Value::Number(n) => n.as_f64(),
+                Value::String(s) => s.trim().parse::<f64>().ok(),
+                _ => None,
+            }
+        }
+    };
+    number.filter(|n| n.is_finite())
+}
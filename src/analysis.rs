@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+use zenoh::time::Timestamp;
+
+// Bookkeeping of the replies received for a query, used to find out who
+// answered, how fast, and whether the answers agree.
+
+#[derive(Clone, Debug)]
+pub struct ReplyRecord {
+    pub replier: String,
+    pub key: String,
+    pub value: String,
+    pub timestamp: Option<Timestamp>,
+    pub latency: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReplyErrorRecord {
+    pub replier: String,
+    pub message: String,
+    pub latency: Duration,
+}
+
+#[derive(Default)]
+pub struct QueryAnalysis {
+    pub replies: Vec<ReplyRecord>,
+    pub errors: Vec<ReplyErrorRecord>,
+}
+
+impl QueryAnalysis {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, reply: ReplyRecord) {
+        self.replies.push(reply);
+    }
+
+    pub fn record_error(&mut self, error: ReplyErrorRecord) {
+        self.errors.push(error);
+    }
+
+    /// Replies grouped by replier, in arrival order.
+    pub fn by_replier(&self) -> BTreeMap<&str, Vec<&ReplyRecord>> {
+        let mut repliers = BTreeMap::<&str, Vec<&ReplyRecord>>::new();
+        for r in &self.replies {
+            repliers.entry(r.replier.as_str()).or_default().push(r);
+        }
+        repliers
+    }
+
+    /// Replies grouped by key, in arrival order.
+    pub fn by_key(&self) -> BTreeMap<&str, Vec<&ReplyRecord>> {
+        let mut keys = BTreeMap::<&str, Vec<&ReplyRecord>>::new();
+        for r in &self.replies {
+            keys.entry(r.key.as_str()).or_default().push(r);
+        }
+        keys
+    }
+
+    /// Keys for which the replies disagree on either the value or the timestamp.
+    pub fn conflicts(&self) -> BTreeMap<&str, Vec<&ReplyRecord>> {
+        self.by_key()
+            .into_iter()
+            .filter(|(_, rs)| {
+                rs.iter()
+                    .any(|r| r.value != rs[0].value || r.timestamp != rs[0].timestamp)
+            })
+            .collect()
+    }
+
+    /// The result a `Latest` consolidation would give, i.e. the most recent
+    /// reply for each key. Replies without a timestamp only win over nothing.
+    pub fn consolidated(&self) -> BTreeMap<&str, &ReplyRecord> {
+        latest_by_key(&self.replies)
+    }
+}
+
+// Keeps the most recent record of each key.
+fn latest_by_key<'a>(
+    records: impl IntoIterator<Item = &'a ReplyRecord>,
+) -> BTreeMap<&'a str, &'a ReplyRecord> {
+    let mut latest = BTreeMap::<&str, &ReplyRecord>::new();
+    for r in records {
+        match latest.get(r.key.as_str()) {
+            Some(current) if current.timestamp >= r.timestamp => {}
+            _ => {
+                latest.insert(r.key.as_str(), r);
+            }
+        }
+    }
+    latest
+}
+
+// Comparison of the content returned by several replicas of the same storage.
+
+#[derive(Clone, Debug)]
+pub struct MissingKey {
+    pub key: String,
+    pub replier: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct StaleKey {
+    pub key: String,
+    pub replier: String,
+    pub timestamp: Option<Timestamp>,
+    pub latest: Option<Timestamp>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ConflictingKey {
+    pub key: String,
+    pub timestamp: Option<Timestamp>,
+    pub values: Vec<(String, String)>,
+}
+
+#[derive(Default)]
+pub struct ConsistencyReport {
+    pub replicas: Vec<String>,
+    pub keys: usize,
+    pub missing: Vec<MissingKey>,
+    pub stale: Vec<StaleKey>,
+    pub conflicts: Vec<ConflictingKey>,
+}
+
+impl ConsistencyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.stale.is_empty() && self.conflicts.is_empty()
+    }
+}
+
+impl QueryAnalysis {
+    /// Compares the replies of each replier, taken as a replica, against the
+    /// others. For every key, replicas holding an older timestamp than the most
+    /// recent one are stale, while replicas agreeing on the most recent
+    /// timestamp but not on the value are in conflict.
+    pub fn consistency(&self) -> ConsistencyReport {
+        // Latest record of each key, per replica
+        let replicas: BTreeMap<&str, BTreeMap<&str, &ReplyRecord>> = self
+            .by_replier()
+            .into_iter()
+            .map(|(replier, replies)| (replier, latest_by_key(replies)))
+            .collect();
+
+        let mut report = ConsistencyReport {
+            replicas: replicas.keys().map(|r| r.to_string()).collect(),
+            ..Default::default()
+        };
+        let keys = self.by_key();
+        report.keys = keys.len();
+        for key in keys.keys() {
+            let held: Vec<(&str, &ReplyRecord)> = replicas
+                .iter()
+                .filter_map(|(replier, content)| content.get(key).map(|r| (*replier, *r)))
+                .collect();
+            for replier in replicas.keys() {
+                if !held.iter().any(|(r, _)| r == replier) {
+                    report.missing.push(MissingKey {
+                        key: key.to_string(),
+                        replier: replier.to_string(),
+                    });
+                }
+            }
+
+            let latest = held.iter().map(|(_, r)| r.timestamp).max().flatten();
+            let (up_to_date, stale): (Vec<_>, Vec<_>) =
+                held.iter().partition(|(_, r)| r.timestamp == latest);
+            for (replier, r) in stale {
+                report.stale.push(StaleKey {
+                    key: key.to_string(),
+                    replier: replier.to_string(),
+                    timestamp: r.timestamp,
+                    latest,
+                });
+            }
+            if up_to_date.iter().any(|(_, r)| r.value != up_to_date[0].1.value) {
+                report.conflicts.push(ConflictingKey {
+                    key: key.to_string(),
+                    timestamp: latest,
+                    values: up_to_date
+                        .iter()
+                        .map(|(replier, r)| (replier.to_string(), r.value.clone()))
+                        .collect(),
+                });
+            }
+        }
+        report
+    }
+}
@@ -0,0 +1,79 @@
+use crate::hook::spawn_with_input;
+use crate::script::{ScriptInput, ScriptOutput};
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+// External commands answering queries. The command is run through `sh -c`
+// for every query, with the query as environment variables (ZSAK_KEY,
+// ZSAK_PARAMETERS, ZSAK_ENCODING, ZSAK_ATTACHMENT) and its payload on stdin.
+// The output of the command is the reply, while a non-zero exit status turns
+// its stderr into an error reply.
+
+#[derive(Clone)]
+pub struct CommandRuntime {
+    cmd: String,
+    permits: Arc<Semaphore>,
+    timeout: Duration,
+}
+
+impl CommandRuntime {
+    pub fn new(cmd: &str, concurrency: usize, timeout: Duration) -> Self {
+        Self {
+            cmd: cmd.to_string(),
+            permits: Arc::new(Semaphore::new(concurrency.max(1))),
+            timeout,
+        }
+    }
+
+    pub async fn run(&self, input: ScriptInput) -> Result<ScriptOutput, String> {
+        // Queries beyond the concurrency limit wait for a running command to
+        // complete.
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let parameters = input
+            .parameters
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join(";");
+        let env = [
+            ("ZSAK_KEY", input.key_expr),
+            ("ZSAK_PARAMETERS", parameters),
+            ("ZSAK_ENCODING", input.encoding.unwrap_or_default()),
+            (
+                "ZSAK_ATTACHMENT",
+                input
+                    .attachment
+                    .map(|a| String::from_utf8_lossy(&a).to_string())
+                    .unwrap_or_default(),
+            ),
+        ];
+        let child = spawn_with_input(&self.cmd, env, input.payload, true)?;
+
+        let output = tokio::time::timeout(self.timeout, child.wait_with_output())
+            .await
+            .map_err(|_| format!("\"{}\" did not complete within {:?}", self.cmd, self.timeout))?
+            .map_err(|e| e.to_string())?;
+        if output.status.success() {
+            Ok(ScriptOutput {
+                replies: vec![(None, output.stdout)],
+                ..Default::default()
+            })
+        } else {
+            let mut error = output.stderr;
+            if error.is_empty() {
+                error = format!("\"{}\" exited with {}", self.cmd, output.status).into_bytes();
+            }
+            Ok(ScriptOutput {
+                error: Some(error),
+                ..Default::default()
+            })
+        }
+    }
+}
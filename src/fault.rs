@@ -0,0 +1,53 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::time::Duration;
+
+// Faults injected by the queryables to test how clients cope with a slow or
+// unreliable service: delays before replying, and the probability of
+// answering with an error or not answering at all.
+
+/// A fixed delay or a range to pick a delay from, in milliseconds, given
+/// either as `100` or `50..200`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Delay(RangeInclusive<u64>);
+
+impl Delay {
+    pub fn pick(&self) -> Duration {
+        Duration::from_millis(rand::random_range(self.0.clone()))
+    }
+}
+
+impl FromStr for Delay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |ms: &str| {
+            ms.trim()
+                .parse::<u64>()
+                .map_err(|_| format!("Invalid delay \"{}\", expected <ms> or <min..max>", s))
+        };
+        match s.split_once("..") {
+            Some((min, max)) => {
+                let (min, max) = (parse(min)?, parse(max)?);
+                if min > max {
+                    return Err(format!("Invalid delay \"{}\", min is above max", s));
+                }
+                Ok(Delay(min..=max))
+            }
+            None => parse(s).map(|ms| Delay(ms..=ms)),
+        }
+    }
+}
+
+/// Parses a probability, between 0 and 1.
+pub fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(p) if (0.0..=1.0).contains(&p) => Ok(p),
+        _ => Err(format!("Invalid rate \"{}\", expected a number between 0 and 1", s)),
+    }
+}
+
+/// Draws whether an event happening with probability `rate` happens.
+pub fn happens(rate: f64) -> bool {
+    rate > 0.0 && rand::random::<f64>() < rate
+}
@@ -72,11 +72,17 @@ pub enum Message {
     DeleteRun,
     DeleteResult(String),
     SubscribeKeyChanged(String),
+    SubscribeVerboseToggled(bool),
     SubscribeStart,
     SubscribeStop,
     QueryExprChanged(String),
     QueryBodyChanged(String),
+    QueryParamNameChanged(usize, String),
+    QueryParamValueChanged(usize, String),
+    QueryParamAdd,
+    QueryParamRemove(usize),
     QueryRun,
+    QueryStop,
     QueryResult(Vec<String>),
     QueryableKeyChanged(String),
     QueryableReplyChanged(String),
@@ -116,8 +122,11 @@ pub struct AppState {
     pub delete_key: String,
     pub subscribe_key: String,
     pub subscribe_active: bool,
+    pub subscribe_verbose: bool,
     pub query_expr: String,
     pub query_body: String,
+    pub query_params: Vec<(String, String)>,
+    pub query_active: bool,
     pub queryable_key: String,
     pub queryable_reply: String,
     pub queryable_active: bool,
@@ -149,8 +158,11 @@ impl Default for AppState {
             delete_key: String::new(),
             subscribe_key: "demo/**".into(),
             subscribe_active: false,
+            subscribe_verbose: false,
             query_expr: "demo/**".into(),
             query_body: String::new(),
+            query_params: Vec::new(),
+            query_active: false,
             queryable_key: "demo/queryable".into(),
             queryable_reply: "pong".into(),
             queryable_active: false,
@@ -227,20 +239,46 @@ impl AppState {
             Message::StreamEvent(ev) => {
                 use ZenohEvent::*;
                 match ev {
-                    Sample { key, value, attachment, n } => {
-                        if let Some(a) = attachment {
-                            self.log_lines.push(format!("[{}] {}: {} (attach: {})", n, key, value, a));
+                    Sample { key, value, attachment, meta, n } => {
+                        if meta.kind == zenoh::sample::SampleKind::Delete {
+                            self.log_lines.push(format!("[{}] DELETE {}", n, key));
+                        } else if let Some(a) = attachment {
+                            self.log_lines.push(format!("[{}] PUT {}: {} (attach: {})", n, key, value, a));
                         } else {
-                            self.log_lines.push(format!("[{}] {}: {}", n, key, value));
+                            self.log_lines.push(format!("[{}] PUT {}: {}", n, key, value));
+                        }
+                        if self.subscribe_verbose {
+                            self.log_lines.push(format!(
+                                "      ts: {}  enc: {}  prio: {:?}  cc: {:?}  express: {}  src: {}  size: {}",
+                                meta.timestamp.as_deref().unwrap_or("None"),
+                                meta.encoding,
+                                meta.priority,
+                                meta.congestion_control,
+                                meta.express,
+                                meta.source_id.as_deref().unwrap_or("Unknown"),
+                                meta.size,
+                            ));
                         }
                     }
                     QueryIn { key, n } => {
                         self.log_lines.push(format!("[{}] Query: {}", n, key));
                     }
+                    ReplyError { replier, value, encoding, n } => {
+                        self.log_lines.push(format!(
+                            "[{}] ERROR from {}: {} ({})",
+                            n,
+                            replier.as_deref().unwrap_or("Unknown"),
+                            value,
+                            encoding,
+                        ));
+                    }
                     LivelinessJoin(k) => self.log_lines.push(format!("JOIN  {}", k)),
                     LivelinessLeave(k) => self.log_lines.push(format!("LEAVE {}", k)),
                     Error(e) => self.log_lines.push(format!("ERROR: {}", e)),
-                    Done => self.log_lines.push("Done.".into()),
+                    Done => {
+                        self.query_active = false;
+                        self.log_lines.push("Done.".into());
+                    }
                 }
                 Task::none()
             }
@@ -370,6 +408,7 @@ impl AppState {
 
             // Subscribe
             Message::SubscribeKeyChanged(v) => { self.subscribe_key = v; Task::none() }
+            Message::SubscribeVerboseToggled(v) => { self.subscribe_verbose = v; Task::none() }
             Message::SubscribeStart => {
                 if let Some(ref z) = self.session {
                     let (task, cancel) = crate::bridge::subscribe_stream(
@@ -390,24 +429,41 @@ impl AppState {
             // Query
             Message::QueryExprChanged(v) => { self.query_expr = v; Task::none() }
             Message::QueryBodyChanged(v) => { self.query_body = v; Task::none() }
+            Message::QueryParamNameChanged(i, v) => {
+                if let Some(param) = self.query_params.get_mut(i) { param.0 = v; }
+                Task::none()
+            }
+            Message::QueryParamValueChanged(i, v) => {
+                if let Some(param) = self.query_params.get_mut(i) { param.1 = v; }
+                Task::none()
+            }
+            Message::QueryParamAdd => { self.query_params.push((String::new(), String::new())); Task::none() }
+            Message::QueryParamRemove(i) => {
+                if i < self.query_params.len() { self.query_params.remove(i); }
+                Task::none()
+            }
             Message::QueryRun => {
                 if let Some(ref z) = self.session {
-                    let z = z.clone();
                     let p = zsak::types::QueryParams {
                         query_expr: self.query_expr.clone(),
                         body: if self.query_body.is_empty() { None } else { Some(self.query_body.clone()) },
+                        parameters: self.query_params.iter()
+                            .filter(|(name, _)| !name.is_empty())
+                            .cloned()
+                            .collect(),
                         ..Default::default()
                     };
-                    Task::future(async move {
-                        let events = zsak::action::do_query_with(&z, &p).await;
-                        let lines = events.iter().map(|e| format!("{:?}", e)).collect();
-                        Message::QueryResult(lines)
-                    })
+                    let (task, cancel) = crate::bridge::query_stream(z.clone(), p);
+                    self.cancel_tx = Some(cancel);
+                    self.query_active = true;
+                    self.log_lines.clear();
+                    task
                 } else {
                     self.log_lines.push("Not connected.".into());
                     Task::none()
                 }
             }
+            Message::QueryStop => { self.stop_stream(); Task::none() }
             Message::QueryResult(lines) => {
                 self.log_lines.clear();
                 self.log_lines.extend(lines);
@@ -4,7 +4,7 @@ use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::StreamExt;
 use iced::Task;
 
-use zsak::types::{QueryableParams, SubscribeParams, ZenohEvent};
+use zsak::types::{QueryParams, QueryableParams, SubscribeParams, ZenohEvent};
 use crate::app::Message;
 
 pub fn subscribe_stream(
@@ -20,6 +20,19 @@ pub fn subscribe_stream(
     (Task::stream(stream), cancel_tx)
 }
 
+pub fn query_stream(
+    session: Arc<zenoh::Session>,
+    params: QueryParams,
+) -> (Task<Message>, oneshot::Sender<()>) {
+    let (event_tx, event_rx) = mpsc::channel::<ZenohEvent>(64);
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+    tokio::spawn(async move {
+        zsak::action::do_query_streaming(&session, params, event_tx, cancel_rx).await;
+    });
+    let stream = ReceiverStream::new(event_rx).map(Message::StreamEvent);
+    (Task::stream(stream), cancel_tx)
+}
+
 pub fn queryable_stream(
     session: Arc<zenoh::Session>,
     params: QueryableParams,
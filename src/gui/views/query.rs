@@ -5,6 +5,19 @@ use crate::app::{AppState, Message};
 use crate::widgets::output_log::output_log;
 
 pub fn view(state: &AppState) -> Element<Message> {
+    let params = column(
+        state.query_params.iter().enumerate().map(|(i, (name, value))| {
+            row![
+                text_input("name", name)
+                    .on_input(move |v| Message::QueryParamNameChanged(i, v))
+                    .width(160),
+                text_input("value", value)
+                    .on_input(move |v| Message::QueryParamValueChanged(i, v)),
+                button("Remove").on_press(Message::QueryParamRemove(i)),
+            ].spacing(8).align_y(iced::Alignment::Center).into()
+        }),
+    ).spacing(4);
+
     column![
         text("Query").size(20),
         row![
@@ -17,7 +30,16 @@ pub fn view(state: &AppState) -> Element<Message> {
             text_input("(optional)", &state.query_body)
                 .on_input(Message::QueryBodyChanged),
         ].spacing(8).align_y(iced::Alignment::Center),
-        button("Query").on_press(Message::QueryRun),
+        row![
+            text("Parameters:").width(80),
+            button("Add").on_press(Message::QueryParamAdd),
+        ].spacing(8).align_y(iced::Alignment::Center),
+        params,
+        if state.query_active {
+            button("Cancel").on_press(Message::QueryStop)
+        } else {
+            button("Query").on_press(Message::QueryRun)
+        },
         output_log(&state.log_lines),
     ]
     .spacing(10)
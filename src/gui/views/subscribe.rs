@@ -1,4 +1,4 @@
-use iced::widget::{button, column, row, text, text_input};
+use iced::widget::{button, checkbox, column, row, text, text_input};
 use iced::Element;
 
 use crate::app::{AppState, Message};
@@ -18,6 +18,8 @@ pub fn view(state: &AppState) -> Element<Message> {
             text_input("demo/**", &state.subscribe_key)
                 .on_input(Message::SubscribeKeyChanged),
         ].spacing(8).align_y(iced::Alignment::Center),
+        checkbox("Show metadata", state.subscribe_verbose)
+            .on_toggle(Message::SubscribeVerboseToggled),
         toggle_btn,
         output_log(&state.log_lines),
     ]
@@ -0,0 +1,136 @@
+use crate::script::{SampleInput, ScriptInput, ScriptRuntime};
+use crate::types::SampleContext;
+
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, Command};
+
+// Hooks run for every sample received by a subscriber. Commands are run
+// through `sh -c` and get the sample context as environment variables
+// (ZSAK_KEY, ZSAK_KIND, ZSAK_TIMESTAMP, ZSAK_ENCODING, ZSAK_ATTACHMENT) and the
+// payload on stdin. Streaming hooks instead keep a single process alive and
+// write one payload per line on its stdin. Scripts run on the same runtimes
+// as the queryable scripts, off the tokio executor. Commands and scripts are
+// given a timeout, so that a hung one does not stop the subscriber.
+
+pub enum SampleHook {
+    Exec(String, Duration),
+    Stream(Child),
+    Script(ScriptRuntime),
+}
+
+impl SampleHook {
+    pub fn exec(cmd: &str, timeout: Duration) -> Self {
+        SampleHook::Exec(cmd.to_string(), timeout)
+    }
+
+    pub fn stream(cmd: &str) -> Result<Self, String> {
+        shell(cmd)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map(SampleHook::Stream)
+            .map_err(|e| format!("Unable to spawn \"{}\": {}", cmd, e))
+    }
+
+    pub fn script(runtime: ScriptRuntime) -> Self {
+        SampleHook::Script(runtime)
+    }
+
+    pub async fn run(
+        &mut self,
+        ctx: &SampleContext,
+        payload: &[u8],
+        attachment: Option<&[u8]>,
+    ) -> Result<(), String> {
+        match self {
+            SampleHook::Exec(cmd, timeout) => {
+                let env = [
+                    ("ZSAK_KEY", ctx.key.clone()),
+                    ("ZSAK_KIND", ctx.kind.clone()),
+                    ("ZSAK_TIMESTAMP", ctx.timestamp.clone().unwrap_or_default()),
+                    ("ZSAK_ENCODING", ctx.encoding.clone()),
+                    (
+                        "ZSAK_ATTACHMENT",
+                        attachment
+                            .map(|a| String::from_utf8_lossy(a).to_string())
+                            .unwrap_or_default(),
+                    ),
+                ];
+                let mut child = spawn_with_input(cmd, env, payload.to_vec(), false)?;
+                // A command still running past the timeout is killed when
+                // the child is dropped.
+                let status = tokio::time::timeout(*timeout, child.wait())
+                    .await
+                    .map_err(|_| format!("\"{}\" did not complete within {:?}", cmd, timeout))?
+                    .map_err(|e| e.to_string())?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(format!("\"{}\" exited with {}", cmd, status))
+                }
+            }
+            SampleHook::Stream(child) => {
+                let stdin = child
+                    .stdin
+                    .as_mut()
+                    .ok_or_else(|| "Streaming process has no stdin".to_string())?;
+                stdin
+                    .write_all(payload)
+                    .await
+                    .and(stdin.write_all(b"\n").await)
+                    .and(stdin.flush().await)
+                    .map_err(|e| format!("Streaming process is gone: {}", e))
+            }
+            SampleHook::Script(runtime) => {
+                let input = ScriptInput {
+                    key_expr: ctx.key.clone(),
+                    parameters: Default::default(),
+                    payload: payload.to_vec(),
+                    encoding: Some(ctx.encoding.clone()),
+                    attachment: attachment.map(<[u8]>::to_vec),
+                    sample: Some(SampleInput {
+                        kind: ctx.kind.clone(),
+                        timestamp: ctx.timestamp.clone(),
+                    }),
+                };
+                match runtime.run(input).await?.error {
+                    Some(e) => Err(String::from_utf8_lossy(&e).into_owned()),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+fn shell(cmd: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(cmd);
+    command
+}
+
+// Spawns a command through `sh -c` with the given environment, writing the
+// payload on its stdin in the background. The command is killed when the
+// child is dropped, and its output is piped back when `capture` is set.
+pub(crate) fn spawn_with_input<'a>(
+    cmd: &str,
+    env: impl IntoIterator<Item = (&'a str, String)>,
+    payload: Vec<u8>,
+    capture: bool,
+) -> Result<Child, String> {
+    let mut command = shell(cmd);
+    command.envs(env).stdin(Stdio::piped()).kill_on_drop(true);
+    if capture {
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Unable to spawn \"{}\": {}", cmd, e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The command may legitimately ignore its stdin.
+        tokio::spawn(async move {
+            let _ = stdin.write_all(&payload).await;
+        });
+    }
+    Ok(child)
+}
@@ -1,3 +1,15 @@
 pub mod action;
+pub mod aggregate;
+pub mod analysis;
+pub mod command;
+pub mod fault;
+pub mod hook;
+pub mod mock;
 pub mod parser;
+pub mod script;
+pub mod selector;
+pub mod serve;
+pub mod snapshot;
+pub mod transform;
+pub mod tree;
 pub mod types;
@@ -80,6 +80,47 @@ async fn main() {
             action::do_subscribe(&z, sub_matches).await;
             false
         }
+        Some(("check-consistency", sub_matches)) => {
+            if !action::do_check_consistency(&z, sub_matches).await {
+                std::process::exit(1);
+            }
+            false
+        }
+        Some(("export", sub_matches)) => {
+            action::do_export(&z, sub_matches).await;
+            false
+        }
+        Some(("import", sub_matches)) => {
+            action::do_import(&z, sub_matches).await;
+            false
+        }
+        Some(("cp", sub_matches)) => {
+            let mut dst_config = match sub_matches.get_one::<String>("dst-config") {
+                Some(fname) => zenoh::Config::from_file(fname)
+                    .expect("Unable to open the destination Zenoh Config"),
+                None => {
+                    // Without an explicit configuration, scouting could end up
+                    // connecting the destination session to the source network.
+                    let mut c = zenoh::Config::default();
+                    c.insert_json5("scouting/multicast/enabled", "false")
+                        .unwrap();
+                    c
+                }
+            };
+            set_required_options(&mut dst_config);
+            if let Some(es) = sub_matches.get_one::<String>("dst-endpoints") {
+                dst_config.insert_json5("connect/endpoints", es).unwrap();
+            }
+            let dst = zenoh::open(dst_config)
+                .await
+                .expect("Unable to open the destination Zenoh Session");
+            action::do_cp(&z, &dst, sub_matches).await;
+            false
+        }
+        Some(("tree", sub_matches)) => {
+            action::do_tree(&z, sub_matches).await;
+            false
+        }
         Some(("query", sub_matches)) => {
             action::do_query(&z, sub_matches).await;
             false
@@ -89,6 +130,16 @@ async fn main() {
             action::do_queryable(&z, sub_matches).await;
             false
         }
+        Some(("mock", sub_matches)) => {
+            println!("Ctrl-C to quit");
+            action::do_mock(&z, sub_matches).await;
+            false
+        }
+        Some(("serve-dir", sub_matches)) => {
+            println!("Ctrl-C to quit");
+            action::do_serve_dir(&z, sub_matches).await;
+            false
+        }
         Some(("stream", _sub_matches)) => {
             if cfg!(feature = "video") {
                 println!("Not Implemented Yet");
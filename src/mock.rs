@@ -0,0 +1,301 @@
+use crate::fault::{Delay, parse_rate};
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+// Rules of the mock queryable. Each rule answers the queries on a key
+// expression, optionally only those carrying some selector parameters or
+// payload, with a reply rendered from a template. Templates can refer to the
+// chunks of the query key matched by the wildcards of the rule as `${1}`,
+// `${2}`..., to the query key as `${key}`, to a selector parameter as
+// `${param:name}` and to the query payload as `${payload}`.
+
+#[derive(Clone, Debug, Default)]
+pub struct MockRule {
+    pub name: String,
+    pub key_expr: String,
+    pub parameters: BTreeMap<String, String>,
+    pub payload: Option<String>,
+    pub reply: String,
+    pub encoding: Option<String>,
+    pub latency: Option<Delay>,
+    pub drop_rate: f64,
+    pub error: Option<String>,
+    pub error_rate: f64,
+}
+
+/// Reads the rules out of a JSON5 document, either an array of rules or an
+/// object holding them under `rules`.
+pub fn parse_rules(content: &str) -> Result<Vec<MockRule>, String> {
+    let doc: Value = json5::from_str(content).map_err(|e| e.to_string())?;
+    let rules = match doc {
+        Value::Array(rules) => rules,
+        Value::Object(mut doc) => match doc.remove("rules") {
+            Some(Value::Array(rules)) => rules,
+            _ => return Err("The rules should be given as an array under \"rules\"".into()),
+        },
+        _ => return Err("The rules should be given as an array".into()),
+    };
+    rules
+        .iter()
+        .enumerate()
+        .map(|(i, rule)| MockRule::from_json(rule).map_err(|e| format!("Rule {}: {}", i, e)))
+        .collect()
+}
+
+impl MockRule {
+    fn from_json(v: &Value) -> Result<Self, String> {
+        let string = |name: &str| -> Result<Option<String>, String> {
+            match v.get(name) {
+                None | Some(Value::Null) => Ok(None),
+                Some(Value::String(s)) => Ok(Some(s.clone())),
+                Some(other) if name == "reply" => Ok(Some(other.to_string())),
+                Some(_) => Err(format!("\"{}\" should be a string", name)),
+            }
+        };
+        let rate = |name: &str| -> Result<Option<f64>, String> {
+            match v.get(name) {
+                None | Some(Value::Null) => Ok(None),
+                Some(Value::Bool(b)) => Ok(Some(if *b { 1.0 } else { 0.0 })),
+                Some(Value::Number(n)) => parse_rate(&n.to_string()).map(Some),
+                Some(_) => Err(format!("\"{}\" should be a number between 0 and 1", name)),
+            }
+        };
+
+        let key_expr = string("key_expr")?.ok_or("Missing \"key_expr\"")?;
+        let parameters = match v.get("parameters") {
+            None | Some(Value::Null) => BTreeMap::new(),
+            Some(Value::Object(params)) => params
+                .iter()
+                .map(|(name, value)| match value {
+                    Value::String(s) => (name.clone(), s.clone()),
+                    other => (name.clone(), other.to_string()),
+                })
+                .collect(),
+            Some(_) => return Err("\"parameters\" should be an object".into()),
+        };
+        let latency = match v.get("latency") {
+            None | Some(Value::Null) => None,
+            Some(Value::Number(n)) => Some(n.to_string().parse::<Delay>()?),
+            Some(Value::String(s)) => Some(s.parse::<Delay>()?),
+            Some(_) => return Err("\"latency\" should be <ms> or \"<min..max>\"".into()),
+        };
+        let error = string("error")?;
+        let error_rate = rate("error_rate")?.unwrap_or(if error.is_some() { 1.0 } else { 0.0 });
+        Ok(MockRule {
+            name: string("name")?.unwrap_or_else(|| key_expr.clone()),
+            key_expr,
+            parameters,
+            payload: string("payload")?,
+            reply: string("reply")?.unwrap_or_default(),
+            encoding: string("encoding")?,
+            latency,
+            drop_rate: rate("drop_rate")?.unwrap_or_default(),
+            error,
+            error_rate,
+        })
+    }
+
+    /// Checks a query against the rule, returning the chunks of the key
+    /// matched by the wildcards of the rule when it applies. A `*` parameter
+    /// value only requires the parameter to be present.
+    pub fn matches(
+        &self,
+        key: &str,
+        parameters: &BTreeMap<String, String>,
+        payload: &str,
+    ) -> Option<Vec<String>> {
+        let params_match = self.parameters.iter().all(|(name, expected)| {
+            parameters
+                .get(name)
+                .is_some_and(|value| expected == "*" || value == expected)
+        });
+        let payload_match = self
+            .payload
+            .as_ref()
+            .is_none_or(|expected| payload.contains(expected.as_str()));
+        if !params_match || !payload_match {
+            return None;
+        }
+        let pattern: Vec<&str> = self.key_expr.split('/').collect();
+        let chunks: Vec<&str> = key.split('/').collect();
+        let mut captures = Vec::new();
+        capture(&pattern, &chunks, &mut captures).then_some(captures)
+    }
+}
+
+/// Renders a reply template for the wildcard chunks captured by a rule.
+pub fn render(
+    template: &str,
+    captures: &[String],
+    key: &str,
+    parameters: &BTreeMap<String, String>,
+    payload: &str,
+) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        let name = &rest[start + 2..start + end];
+        let value = match name {
+            "key" => Some(key.to_string()),
+            "payload" => Some(payload.to_string()),
+            _ => match name.strip_prefix("param:") {
+                Some(param) => Some(parameters.get(param).cloned().unwrap_or_default()),
+                None => name
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| i.checked_sub(1))
+                    .map(|i| captures.get(i).cloned().unwrap_or_default()),
+            },
+        };
+        match value {
+            Some(value) => out.push_str(&value),
+            // Unknown placeholders are kept as is
+            None => out.push_str(&rest[start..=start + end]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+// Matches the chunks of a key against the chunks of a pattern, collecting the
+// chunks matched by each wildcard. A `**` captures the chunks it spans joined
+// by `/`, possibly none. Likewise a `**` in the query key spans any number of
+// chunks of the pattern, the wildcards among them capturing `**`.
+fn capture(pattern: &[&str], chunks: &[&str], captures: &mut Vec<String>) -> bool {
+    if let Some((&"**", rest)) = chunks.split_first()
+        && pattern.first() != Some(&"**")
+    {
+        for i in 0..=pattern.len() {
+            let mut attempt = captures.clone();
+            attempt.extend(
+                pattern[..i]
+                    .iter()
+                    .filter(|p| p.contains('*'))
+                    .map(|_| "**".to_string()),
+            );
+            if capture(&pattern[i..], rest, &mut attempt) {
+                *captures = attempt;
+                return true;
+            }
+        }
+        return false;
+    }
+    match pattern.split_first() {
+        None => chunks.is_empty(),
+        Some((&"**", rest)) => {
+            for i in 0..=chunks.len() {
+                let mut attempt = captures.clone();
+                attempt.push(chunks[..i].join("/"));
+                if capture(rest, &chunks[i..], &mut attempt) {
+                    *captures = attempt;
+                    return true;
+                }
+            }
+            false
+        }
+        Some((p, rest)) => match chunks.split_first() {
+            Some((c, chunks)) if *p == "*" || chunk_matches(p, c) || *c == "*" => {
+                if p.contains('*') {
+                    captures.push(c.to_string());
+                }
+                capture(rest, chunks, captures)
+            }
+            _ => false,
+        },
+    }
+}
+
+// A chunk of the pattern possibly holding `$*` sub-chunk wildcards.
+fn chunk_matches(pattern: &str, chunk: &str) -> bool {
+    match pattern.split_once("$*") {
+        None => pattern == chunk,
+        Some((prefix, suffix)) => chunk.strip_prefix(prefix).is_some_and(|rest| {
+            (0..=rest.len())
+                .filter(|i| rest.is_char_boundary(*i))
+                .any(|i| chunk_matches(suffix, &rest[i..]))
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(key_expr: &str) -> MockRule {
+        MockRule {
+            key_expr: key_expr.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn captures(key_expr: &str, key: &str) -> Option<Vec<String>> {
+        rule(key_expr).matches(key, &BTreeMap::new(), "")
+    }
+
+    #[test]
+    fn capture_wildcards() {
+        assert_eq!(
+            captures("demo/users/*", "demo/users/alice"),
+            Some(vec!["alice".into()])
+        );
+        assert_eq!(captures("demo/**", "demo/a/b"), Some(vec!["a/b".into()]));
+        assert_eq!(captures("demo/**", "demo"), Some(vec!["".into()]));
+        assert_eq!(captures("demo/u$*", "demo/user"), Some(vec!["user".into()]));
+        assert_eq!(captures("demo/users/*", "demo/orders/1"), None);
+        assert_eq!(captures("demo/users/*", "demo/users/alice/x"), None);
+    }
+
+    #[test]
+    fn capture_query_wildcards() {
+        assert_eq!(
+            captures("demo/users/*", "demo/users/*"),
+            Some(vec!["*".into()])
+        );
+        assert_eq!(captures("demo/users/*", "demo/**"), Some(vec!["**".into()]));
+        assert_eq!(captures("demo/users/*", "**"), Some(vec!["**".into()]));
+        assert_eq!(captures("demo/users", "demo/**/users"), Some(vec![]));
+        assert_eq!(captures("demo/users/*", "other/**"), None);
+    }
+
+    #[test]
+    fn filters() {
+        let mut r = rule("demo/*");
+        r.parameters.insert("format".into(), "json".into());
+        r.payload = Some("id".into());
+        let params = BTreeMap::from([("format".to_string(), "json".to_string())]);
+        assert!(r.matches("demo/a", &params, "{\"id\": 1}").is_some());
+        assert!(r.matches("demo/a", &params, "{}").is_none());
+        assert!(
+            r.matches("demo/a", &BTreeMap::new(), "{\"id\": 1}")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn render_placeholders() {
+        let params = BTreeMap::from([("unit".to_string(), "celsius".to_string())]);
+        let captures = vec!["alice".to_string()];
+        assert_eq!(
+            render(
+                "${1} ${key} ${param:unit} ${payload}",
+                &captures,
+                "demo/alice",
+                &params,
+                "hi"
+            ),
+            "alice demo/alice celsius hi"
+        );
+        assert_eq!(render("${2}${param:none}", &captures, "", &params, ""), "");
+        assert_eq!(
+            render("${other} ${1", &captures, "", &params, ""),
+            "${other} ${1"
+        );
+    }
+}
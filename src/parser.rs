@@ -1,4 +1,4 @@
-use clap::{ArgMatches, Command, arg};
+use clap::{ArgAction, ArgGroup, ArgMatches, Command, arg};
 use std::str::FromStr;
 use tokio::io::AsyncReadExt;
 
@@ -27,14 +27,152 @@ You can also use key expressions, as in:
 
     zenoh subscribe zenoh/*
 
+To start with the values currently held by storages, and then follow the live updates:
+
+    zenoh subscribe --initial-query zenoh/*
+
+JSON payloads can be reshaped or filtered with a jq-like expression, the
+key expression and the timestamp of the sample are available as $key and $timestamp.
+Only the printed output is transformed, --exec and --script still get every sample as received:
+
+    zenoh subscribe --transform '{key: $key, temp: .temperature} | select(.temp > 20)' sensor/**
+
+Each sample can also trigger a command, that receives the payload on stdin and the key, kind,
+timestamp, encoding and attachment in the ZSAK_KEY, ZSAK_KIND, ZSAK_TIMESTAMP, ZSAK_ENCODING
+and ZSAK_ATTACHMENT environment variables. Commands running past --timeout milliseconds are
+killed:
+
+    zenoh subscribe --exec 'logger -t zenoh "$ZSAK_KEY changed"' sensor/**
+
+Alternatively, a python or rhai script can be executed for each sample, with the key_expr,
+kind, timestamp, encoding, payload and attachment variables, the engine being picked from the
+extension of the script unless given with --engine. Setting error reports a failure, and
+scripts running past --timeout milliseconds are interrupted:
+
+    zenoh subscribe --script alert.py sensor/**
+    zenoh subscribe --script alert.rhai sensor/**
+
+"#;
+
+const TREE_AFTER_HELP: &str = r#"
+The tree combines the keys returned by storages and queryables with the ones observed
+through a live subscription, showing for each key its last value, number of updates
+and age:
+
+    zenoh tree demo
+
+To simply print the keys currently available under the prefix:
+
+    zenoh tree --snapshot demo
+
+"#;
+
+const CP_AFTER_HELP: &str = r#"
+The source network is the one configured through the global options, while the destination is
+configured with --dst-config or --dst-endpoints. For instance, to copy the demo keys of the local
+network under the staging prefix of another network:
+
+    zenoh cp --dst-endpoints '["tcp/10.0.1.1:7447"]' --from demo --to staging/demo demo/**
+
 "#;
 
 const QUERY_AFTER_HELP: &str = r#"
-ADD HERE FEW EXAMPLES
+To query the values available for a key expression:
+
+    zenoh query demo/**
+
+Selector parameters can be added with --param, while --since and --until restrict the
+time range of the values asked to the storages:
+
+    zenoh query --param format=json --since 1h demo/**
+
+To find out who answered a query, how fast, and whether the answers agree:
+
+    zenoh query --target all --analyze demo/**
+
+To keep watching the results, re-issuing the query every 5 seconds:
+
+    zenoh query --watch 5 demo/**
+
+To average the temperature field of the JSON replies, per sensor:
+
+    zenoh query --aggregate avg --field temperature --group-by 1 'sensor/*/temp'
+
 "#;
 
 const QUERYABLE_AFTER_HELP: &str = r#"
-ADD HERE FEW EXAMPLES
+To answer the queries on demo/hello with a fixed reply:
+
+    zenoh queryable demo/hello "Hello World!"
+
+To answer each query with the output of a command, the key expression, selector parameters,
+encoding and attachment of the query being given as the ZSAK_KEY, ZSAK_PARAMETERS, ZSAK_ENCODING
+and ZSAK_ATTACHMENT environment variables, and its payload on stdin:
+
+    zenoh queryable --exec 'cat /proc/loadavg' demo/load
+
+Scripts are written in python or in rhai, depending on their extension or --engine. A rhai
+script sets result to reply, e.g. in echo.rhai:
+
+    result = "Hello " + key_expr;
+
+    zenoh queryable --script --file demo/echo echo.rhai
+
+Python scripts can import the zsak module to use the session of the queryable with put, get and
+delete, keep values between calls in the zsak.state dict, and log with zsak.log:
+
+    zenoh queryable --script --file demo/stats/average script/average.py
+
+To stand in for a storage with a known content, a data file maps each key to its value, a query
+getting one reply per matching key:
+
+    { "demo/rooms/kitchen": "21.5", "demo/rooms/bedroom": { temperature: 19 } }
+
+    zenoh queryable --data rooms.json5 demo/rooms/**
+
+For resilience testing, replies can be delayed, turned into errors or dropped at random:
+
+    zenoh queryable --delay 50..200 --error-rate 0.1 --drop-rate 0.05 demo/flaky "ok"
+
+"#;
+
+const MOCK_AFTER_HELP: &str = r#"
+Each rule answers the queries on its key expression, optionally only those carrying the given
+selector parameters, or a payload containing the given text. The reply can refer to the chunks
+matched by the wildcards of the key expression as ${1}, ${2}..., to the query key as ${key}, to a
+parameter as ${param:name} and to the query payload as ${payload}. Rules can also add latency, in
+milliseconds or as a range, drop queries or answer with errors. When several rules match a query,
+only the first one in the file answers it, so filtered rules go before the plain ones they refine:
+
+    {
+      rules: [
+        {
+          name: "user",
+          key_expr: "demo/users/*",
+          parameters: { format: "json" },
+          reply: '{ "id": "${1}" }',
+          encoding: "application/json",
+          latency: "50..200",
+        },
+        { key_expr: "demo/flaky/**", reply: "ok", drop_rate: 0.2, error: "unavailable", error_rate: 0.1 },
+      ],
+    }
+
+    zenoh mock rules.json5
+
+"#;
+
+const SERVE_DIR_AFTER_HELP: &str = r#"
+Each file of the directory is the value of the key made of the prefix and its relative path, with
+an encoding guessed from its extension. For instance, with the following command, a query on
+config/app/settings.json gets the content of ./config/app/settings.json, while a query on
+config/** gets all the files:
+
+    zenoh serve-dir config ./config
+
+With --write, the values put under the prefix are written back to the directory, and deleted keys
+remove the corresponding files.
+
 "#;
 
 const STORAGE_AFTER_HELP: &str = r#"
@@ -89,15 +227,67 @@ pub fn arg_parser() -> Command {
                         .subcommand(
                 Command::new("delete")
                     .about("Delete data on a given key expression")
+                    .arg(arg!(-r --recursive "Deletes one by one the keys that the storages return for the key expression").required(false))
+                    .arg(arg!(-p --preview "With --recursive, lists the keys and asks for confirmation before deleting them").required(false).requires("recursive"))
+                    .arg(arg!(-y --yes "Skips the confirmation asked by --preview").required(false).requires("recursive"))
                     .arg(arg!(<KEY_EXPR> "The key expression used for the publication").required(true))
             )
             .subcommand(
                 Command::new("subscribe")
                     .alias("sub")
                     .about("Subscribe to the given key expression")
+                    .arg(arg!(-i --"initial-query" "Queries the current values before following the live updates").required(false))
+                    .arg(arg!(-v --verbose "Prints the sample metadata, i.e. timestamp, encoding, QoS, source info and size").required(false))
+                    .arg(arg!(--transform <EXPR> "A jq-like expression applied to each JSON payload before printing").required(false))
+                    .arg(arg!(--exec <CMD> "A shell command run for each sample, receiving the payload on stdin").required(false))
+                    .arg(arg!(--stream "Streams the payloads, one per line, to a single long-lived --exec process").required(false).requires("exec"))
+                    .arg(arg!(--script <FILE> "A python or rhai script run for each sample").required(false).conflicts_with("exec"))
+                    .arg(arg!(--engine <ENGINE> "The scripting engine, should be one of <python|rhai>, guessed from the script extension by default").required(false))
+                    .arg(arg!(-p --packages <PATH> "The path to the python site-packages including required dependencies").required(false))
+                    .arg(arg!(-t --timeout <MILLISECONDS> "The time given to the --exec command or the --script to handle a sample, 10000 by default").required(false))
                     .arg(arg!(<KEY_EXPR> "The key expression used for the publication").required(true))
                     .after_help(SUB_AFTER_HELP),
             )
+            .subcommand(
+                Command::new("check-consistency")
+                    .about("Checks that the replicas of a storage hold the same content, exits with an error otherwise")
+                    .arg(arg!(-n --replicas <NUMBER> "The number of replicas expected to reply, at least 2 by default").required(false))
+                    .arg(arg!(<KEY_EXPR> "The key expression to check").required(true))
+            )
+            .subcommand(
+                Command::new("export")
+                    .about("Saves everything available under a key expression to a directory, or to a .json archive")
+                    .arg(arg!(<KEY_EXPR> "The key expression to export").required(true))
+                    .arg(arg!(<TARGET> "The directory, or the .json file, where the snapshot is written").required(true))
+            )
+            .subcommand(
+                Command::new("import")
+                    .about("Publishes back a snapshot created with export")
+                    .arg(arg!(--from <PREFIX> "The key prefix to replace").required(false))
+                    .arg(arg!(--to <PREFIX> "The key prefix used instead of --from").required(false))
+                    .arg(arg!(--"keep-timestamps" "Publishes the values with their original timestamps rather than new ones").required(false))
+                    .arg(arg!(<SOURCE> "The directory, or the .json file, holding the snapshot").required(true))
+            )
+            .subcommand(
+                Command::new("cp")
+                    .about("Copies the data available under a key expression to another Zenoh network")
+                    .arg(arg!(--"dst-config" <FILE> "The Zenoh configuration used for the destination network").required(false))
+                    .arg(arg!(--"dst-endpoints" <ENDPOINTS> "The array of endpoints of the destination network, e.g. [\"tcp/10.0.1.1:7447\"]").required(false))
+                    .group(ArgGroup::new("destination").args(["dst-config", "dst-endpoints"]).multiple(true).required(true))
+                    .arg(arg!(--from <PREFIX> "The key prefix to replace").required(false))
+                    .arg(arg!(--to <PREFIX> "The key prefix used instead of --from").required(false))
+                    .arg(arg!(--"dry-run" "Lists what would be copied without publishing anything").required(false))
+                    .arg(arg!(<KEY_EXPR> "The key expression to copy").required(true))
+                    .after_help(CP_AFTER_HELP)
+            )
+            .subcommand(
+                Command::new("tree")
+                    .about("Displays the live tree of the keys available under a given prefix")
+                    .arg(arg!(-s --snapshot "Prints the keys currently available and exits").required(false))
+                    .arg(arg!(-r --refresh <SECONDS> "The refresh period of the tree, 2 seconds by default").required(false).value_parser(positive_integer))
+                    .arg(arg!(<PREFIX> "The key prefix to explore").required(true))
+                    .after_help(TREE_AFTER_HELP),
+            )
             .subcommand(
                 Command::new("query")
                     .alias("get")
@@ -105,6 +295,16 @@ pub fn arg_parser() -> Command {
                     .arg(arg!(-f --file "If enabled expects that body/attachment are file names").required(false))
                     .arg(arg!(-t --target <QUERY_TARGET> "Should be one of <best|all|all-complete>, \"best\" used by as the default.").required(false))
                     .arg(arg!(-c --consolidation <CONSOLIDATION> "Should be one of <none|monotonic|latest>,  \"none\" used as the default.").required(false))
+                    .arg(arg!(--timeout <MILLISECONDS> "The query timeout, the Zenoh default is used otherwise").required(false))
+                    .arg(arg!(--analyze "Reports latency, keys and disagreements per replier").required(false))
+                    .arg(arg!(-w --watch <SECONDS> "Re-issues the query periodically, printing only what changed").required(false).value_parser(positive_integer))
+                    .arg(arg!(--aggregate <AGGREGATE> "Aggregates the numeric replies, should be one of <min|max|avg|sum|count>").required(false))
+                    .arg(arg!(--field <FIELD> "The JSON field holding the value to aggregate, e.g. reading.temperature").required(false).requires("aggregate"))
+                    .arg(arg!(--"group-by" <CHUNK> "Groups the aggregate by the key chunk at this position, starting at 0").required(false).requires("aggregate"))
+                    .arg(arg!(--param <PARAM> "A selector parameter given as name=value, can be repeated").required(false).action(ArgAction::Append))
+                    .arg(arg!(--since <TIME> "Only asks for values more recent than TIME, e.g. 1h, now(-2d) or an RFC3339 date").required(false))
+                    .arg(arg!(--until <TIME> "Only asks for values older than TIME, e.g. 10m, now(-1h) or an RFC3339 date").required(false))
+                    .arg(arg!(--transform <EXPR> "A jq-like expression applied to each JSON reply payload before printing").required(false))
                     .arg(arg!(<QUERY_EXPR> "The key expression used for the publication").required(true))
                     .arg(arg!(<BODY> "The value used for this publication").required(false))
                     .arg(arg!(<ATTACHMENT> "The publication attachment, if any").required(false))
@@ -115,12 +315,37 @@ pub fn arg_parser() -> Command {
                     .about("Declares a queryable answering queries for a given key expression")
                     .arg(arg!(-c --complete "Makes the queriable complete").required(false))
                     .arg(arg!(-f --file "If enabled expects that the replt is a file name").required(false))
-                    .arg(arg!(-s --script "the <REPLY> is a python script").required(false))
+                    .arg(arg!(-s --script "the <REPLY> is a python or rhai script").required(false))
+                    .arg(arg!(--engine <ENGINE> "The scripting engine, should be one of <python|rhai>, guessed from the script extension by default").required(false))
+                    .arg(arg!(-e --error "Replies with an error carrying <REPLY> rather than a value").required(false))
+                    .arg(arg!(-x --exec <COMMAND> "A shell command run for each query, its output being the reply").required(false).conflicts_with("script"))
+                    .arg(arg!(-d --data <FILE> "A JSON5 file mapping keys to values, each matching key being a reply").required(false).conflicts_with_all(["script", "exec", "error"]))
                     .arg(arg!(-p --packages <PATH> "The path to the python site-packages including required dependencies").required(false))
+                    .arg(arg!(-w --workers <N> "The number of scripts or commands running at once, 4 by default").required(false))
+                    .arg(arg!(-t --timeout <MILLISECONDS> "The time given to the script or command to answer a query, 10000 by default").required(false))
+                    .arg(arg!(--delay <MILLISECONDS> "Delays the replies, by a fixed time or a random one within a range, e.g. 50..200").required(false))
+                    .arg(arg!(--"error-rate" <RATE> "The probability, between 0 and 1, of answering with an error").required(false))
+                    .arg(arg!(--"drop-rate" <RATE> "The probability, between 0 and 1, of not answering at all").required(false))
                     .arg(arg!(<KEY_EXPR> "The expression associated with the queryable"))
-                    .arg(arg!(<REPLY> "The reply used by the queryable"))
+                    .arg(arg!(<REPLY> "The reply used by the queryable").required(false).required_unless_present_any(["exec", "data"]))
                     .after_help(QUERYABLE_AFTER_HELP)
             )
+            .subcommand(
+                Command::new("mock")
+                    .about("Declares the queryables described by a set of mock rules")
+                    .arg(arg!(-c --complete "Makes the queryables complete").required(false))
+                    .arg(arg!(<RULES> "The JSON5 file holding the mock rules"))
+                    .after_help(MOCK_AFTER_HELP)
+            )
+            .subcommand(
+                Command::new("serve-dir")
+                    .about("Declares a queryable serving the files of a directory under a key prefix")
+                    .arg(arg!(-c --complete "Makes the queryable complete").required(false))
+                    .arg(arg!(-w --write "Writes the values put under the prefix back to the directory").required(false))
+                    .arg(arg!(<PREFIX> "The key prefix the directory is mapped onto"))
+                    .arg(arg!(<DIR> "The directory to serve"))
+                    .after_help(SERVE_DIR_AFTER_HELP)
+            )
             .subcommand(
                 Command::new("storage")
                     .about("Declares an in-memory storage")
@@ -152,6 +377,15 @@ pub fn arg_parser() -> Command {
     }
 }
 
+// Rejects periods of zero, which would spin, while keeping the value as a
+// string like the other arguments.
+fn positive_integer(s: &str) -> Result<String, String> {
+    match s.parse::<u64>() {
+        Ok(n) if n > 0 => Ok(s.to_string()),
+        _ => Err("should be a positive integer".into()),
+    }
+}
+
 pub async fn resolve_argument<T: FromStr>(
     sub_matches: &ArgMatches,
     arg: &str,
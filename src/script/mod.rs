@@ -0,0 +1,179 @@
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "rhai")]
+mod rhai;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+// Scripts answering queries, written either in python or in rhai, an
+// embedded pure-Rust language, depending on the features enabled at build
+// time.
+//
+// Scripts get the query as the `key_expr`, `parameters`, `payload`,
+// `encoding` and `attachment` variables and answer by setting:
+// - `result`, either a string or bytes value replied on the query key
+//   expression, or a list of `(key, value)` pairs for wildcard queries,
+// - `result_encoding` and `result_attachment`, applied to all the replies,
+// - `error`, a string or bytes value sent as an error reply instead of `result`.
+//
+// The same runtimes run the subscriber hooks, which also get the `kind` and
+// `timestamp` of the sample and do not have to set a `result`.
+
+pub struct ScriptInput {
+    pub key_expr: String,
+    pub parameters: BTreeMap<String, String>,
+    pub payload: Vec<u8>,
+    pub encoding: Option<String>,
+    pub attachment: Option<Vec<u8>>,
+    /// Set when running a subscriber hook rather than answering a query.
+    pub sample: Option<SampleInput>,
+}
+
+pub struct SampleInput {
+    pub kind: String,
+    pub timestamp: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ScriptOutput {
+    /// The replies, a `None` key standing for the query key expression.
+    pub replies: Vec<(Option<String>, Vec<u8>)>,
+    pub encoding: Option<String>,
+    pub attachment: Option<Vec<u8>>,
+    pub error: Option<Vec<u8>>,
+}
+
+pub type ScriptResult = Result<ScriptOutput, String>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScriptEngine {
+    Python,
+    Rhai,
+}
+
+impl FromStr for ScriptEngine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "python" => Ok(ScriptEngine::Python),
+            "rhai" => Ok(ScriptEngine::Rhai),
+            _ => Err(format!(
+                "Unknown scripting engine \"{}\", should be one of <python|rhai>",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ScriptEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptEngine::Python => write!(f, "python"),
+            ScriptEngine::Rhai => write!(f, "rhai"),
+        }
+    }
+}
+
+impl ScriptEngine {
+    /// Picks the engine explicitly asked for, or the one matching the
+    /// extension of the script file, python being preferred otherwise.
+    pub fn detect(engine: Option<&str>, path: Option<&str>) -> Result<Self, String> {
+        let ext = path.and_then(|p| Path::new(p).extension()?.to_str());
+        let engine = match (engine, ext) {
+            (Some(engine), _) => engine.parse()?,
+            (None, Some("py")) => ScriptEngine::Python,
+            (None, Some("rhai")) => ScriptEngine::Rhai,
+            (None, _) if cfg!(feature = "python") => ScriptEngine::Python,
+            (None, _) if cfg!(feature = "rhai") => ScriptEngine::Rhai,
+            (None, _) => {
+                return Err("No scripting engine was enabled at build time, \
+                    see the python and rhai features"
+                    .into());
+            }
+        };
+        if !engine.is_enabled() {
+            return Err(format!(
+                "The {} scripting engine was not enabled at build time",
+                engine
+            ));
+        }
+        Ok(engine)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            ScriptEngine::Python => cfg!(feature = "python"),
+            ScriptEngine::Rhai => cfg!(feature = "rhai"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum ScriptRuntime {
+    #[cfg(feature = "python")]
+    Python(python::PythonRuntime),
+    #[cfg(feature = "rhai")]
+    Rhai(self::rhai::RhaiRuntime),
+}
+
+impl ScriptRuntime {
+    #[cfg_attr(
+        not(any(feature = "python", feature = "rhai")),
+        allow(unused_variables)
+    )]
+    pub fn new(
+        #[cfg_attr(not(feature = "python"), allow(unused_variables))] session: &zenoh::Session,
+        engine: ScriptEngine,
+        code: &str,
+        #[cfg_attr(not(feature = "python"), allow(unused_variables))] packages_path: Option<&str>,
+        workers: usize,
+        timeout: Duration,
+    ) -> Result<Self, String> {
+        match engine {
+            #[cfg(feature = "python")]
+            ScriptEngine::Python => {
+                python::PythonRuntime::new(session, code, packages_path, workers, timeout)
+                    .map(ScriptRuntime::Python)
+            }
+            #[cfg(feature = "rhai")]
+            ScriptEngine::Rhai => {
+                self::rhai::RhaiRuntime::new(code, workers, timeout).map(ScriptRuntime::Rhai)
+            }
+            #[cfg(not(all(feature = "python", feature = "rhai")))]
+            _ => unreachable!("{} is rejected by ScriptEngine::detect", engine),
+        }
+    }
+
+    /// Replaces the script run for the next queries. The current script is
+    /// kept when the new one does not compile.
+    #[cfg_attr(
+        not(any(feature = "python", feature = "rhai")),
+        allow(unused_variables)
+    )]
+    pub fn reload(&self, code: &str) -> Result<(), String> {
+        match *self {
+            #[cfg(feature = "python")]
+            ScriptRuntime::Python(ref python) => python.reload(code),
+            #[cfg(feature = "rhai")]
+            ScriptRuntime::Rhai(ref rhai) => rhai.reload(code),
+        }
+    }
+
+    #[cfg_attr(
+        not(any(feature = "python", feature = "rhai")),
+        allow(unused_variables)
+    )]
+    pub async fn run(&self, input: ScriptInput) -> ScriptResult {
+        match *self {
+            #[cfg(feature = "python")]
+            ScriptRuntime::Python(ref python) => python.run(input).await,
+            #[cfg(feature = "rhai")]
+            ScriptRuntime::Rhai(ref rhai) => rhai.run(input).await,
+        }
+    }
+}
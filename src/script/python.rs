@@ -0,0 +1,381 @@
+use super::{ScriptInput, ScriptOutput, ScriptResult};
+
+use pyo3::exceptions::{PyRuntimeError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyList, PyString, PyTuple};
+use std::os::raw::c_long;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, mpsc};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use zenoh::Wait;
+use zenoh::query::Reply;
+
+// Python scripts answering queries. A script is compiled once and run on a
+// pool of dedicated threads, so that a slow script never blocks the tokio
+// executor nor the other queries. Calls running past the timeout are
+// answered with an error right away, and interrupted by raising a
+// TimeoutError in the worker thread. Python only raises it when running
+// bytecode, so a script blocked in C code, e.g. in time.sleep or a socket
+// read, keeps its worker until the blocking call returns.
+//
+// Scripts can also import the zsak module to use the session of the
+// queryable, keep a state between calls and log messages.
+
+struct Job {
+    id: u64,
+    input: ScriptInput,
+    reply: oneshot::Sender<ScriptResult>,
+}
+
+// The Python thread identifier of a worker and the job it is running, if any.
+#[derive(Default)]
+struct WorkerState {
+    thread_id: c_long,
+    job: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct PythonRuntime {
+    code: Arc<Mutex<Py<PyAny>>>,
+    jobs: mpsc::Sender<Job>,
+    workers: Arc<Vec<Mutex<WorkerState>>>,
+    next_id: Arc<AtomicU64>,
+    timeout: Duration,
+}
+
+impl PythonRuntime {
+    pub fn new(
+        session: &zenoh::Session,
+        code: &str,
+        packages_path: Option<&str>,
+        workers: usize,
+        timeout: Duration,
+    ) -> Result<Self, String> {
+        Python::initialize();
+        let code = Python::attach(|py| -> PyResult<Py<PyAny>> {
+            if let Some(path) = packages_path {
+                py.import("sys")?.getattr("path")?.call_method1("append", (path,))?;
+            }
+            install_module(py, session)?;
+            compile(py, code)
+        })
+        .map_err(|e| format!("Invalid script: {}", e))?;
+        let code = Arc::new(Mutex::new(code));
+
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+        let states: Arc<Vec<Mutex<WorkerState>>> = Arc::new(
+            (0..workers.max(1))
+                .map(|_| Mutex::new(WorkerState::default()))
+                .collect(),
+        );
+        for i in 0..states.len() {
+            let (code, queue, states) = (code.clone(), queue.clone(), states.clone());
+            std::thread::Builder::new()
+                .name(format!("zsak-script-{}", i))
+                .spawn(move || run_worker(&code, &queue, &states[i]))
+                .map_err(|e| format!("Unable to start the script workers: {}", e))?;
+        }
+
+        Ok(Self {
+            code,
+            jobs,
+            workers: states,
+            next_id: Arc::new(AtomicU64::new(0)),
+            timeout,
+        })
+    }
+
+    pub fn reload(&self, code: &str) -> Result<(), String> {
+        Python::attach(|py| {
+            let code = compile(py, code).map_err(|e| format!("Invalid script: {}", e))?;
+            *self.code.lock().unwrap() = code;
+            Ok(())
+        })
+    }
+
+    pub async fn run(&self, input: ScriptInput) -> ScriptResult {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (reply, result) = oneshot::channel();
+        self.jobs
+            .send(Job { id, input, reply })
+            .map_err(|_| "The script workers are gone".to_string())?;
+        match tokio::time::timeout(self.timeout, result).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err("The script worker stopped unexpectedly".into()),
+            Err(_) => {
+                let workers = self.workers.clone();
+                let _ = tokio::task::spawn_blocking(move || interrupt(&workers, id)).await;
+                Err(format!("The script did not complete within {:?}", self.timeout))
+            }
+        }
+    }
+}
+
+fn compile(py: Python<'_>, code: &str) -> PyResult<Py<PyAny>> {
+    let compiled = py
+        .import("builtins")?
+        .getattr("compile")?
+        .call1((code, "<queryable>", "exec"))?;
+    Ok(compiled.unbind())
+}
+
+fn run_worker(
+    code: &Mutex<Py<PyAny>>,
+    queue: &Mutex<mpsc::Receiver<Job>>,
+    state: &Mutex<WorkerState>,
+) {
+    let thread_id = Python::attach(|py| -> PyResult<c_long> {
+        py.import("threading")?.getattr("get_ident")?.call0()?.extract()
+    });
+    match thread_id {
+        Ok(thread_id) => state.lock().unwrap().thread_id = thread_id,
+        Err(e) => {
+            eprintln!("Unable to start a script worker: {}", e);
+            return;
+        }
+    }
+
+    loop {
+        let job = match queue.lock().unwrap().recv() {
+            Ok(job) => job,
+            Err(_) => break,
+        };
+        // The query has already timed out while waiting in the queue.
+        if job.reply.is_closed() {
+            continue;
+        }
+        let result = Python::attach(|py| {
+            let code = code.lock().unwrap().clone_ref(py);
+            state.lock().unwrap().job = Some(job.id);
+            let result = execute(py, &code, &job.input);
+            let mut state = state.lock().unwrap();
+            state.job = None;
+            // Discards a timeout raised too late to interrupt this job, so
+            // that it does not hit the next one.
+            unsafe {
+                pyo3::ffi::PyThreadState_SetAsyncExc(state.thread_id, std::ptr::null_mut());
+            }
+            result
+        });
+        let _ = job.reply.send(result);
+    }
+}
+
+fn execute(py: Python<'_>, code: &Py<PyAny>, input: &ScriptInput) -> ScriptResult {
+    let scope = PyDict::new(py);
+    let run = || -> PyResult<()> {
+        scope.set_item("key_expr", &input.key_expr)?;
+        scope.set_item("parameters", &input.parameters)?;
+        scope.set_item("payload", PyBytes::new(py, &input.payload))?;
+        scope.set_item("encoding", &input.encoding)?;
+        scope.set_item(
+            "attachment",
+            input.attachment.as_ref().map(|a| PyBytes::new(py, a)),
+        )?;
+        if let Some(sample) = &input.sample {
+            scope.set_item("kind", &sample.kind)?;
+            scope.set_item("timestamp", &sample.timestamp)?;
+        }
+        py.import("builtins")?
+            .getattr("exec")?
+            .call1((code.bind(py), &scope))?;
+        Ok(())
+    };
+    run().map_err(|e| format_exception(py, &e))?;
+
+    let local = |name: &str| scope.get_item(name).ok().flatten().filter(|v| !v.is_none());
+    let mut output = ScriptOutput {
+        encoding: local("result_encoding")
+            .map(|e| e.extract::<String>())
+            .transpose()
+            .map_err(|_| "The script result_encoding should be a string".to_string())?,
+        attachment: local("result_attachment")
+            .map(|a| to_bytes(&a, "result_attachment"))
+            .transpose()?,
+        error: local("error").map(|e| to_bytes(&e, "error")).transpose()?,
+        ..Default::default()
+    };
+    if output.error.is_some() {
+        return Ok(output);
+    }
+
+    match local("result") {
+        Some(result) if result.is_instance_of::<PyList>() => {
+            for item in result.cast::<PyList>().unwrap().iter() {
+                let pair = item
+                    .cast::<PyTuple>()
+                    .ok()
+                    .filter(|t| t.len() == 2)
+                    .ok_or_else(|| "The script result list should hold (key, value) pairs".to_string())?;
+                let key = pair
+                    .get_item(0)
+                    .and_then(|k| k.extract::<String>())
+                    .map_err(|_| "The script result keys should be strings".to_string())?;
+                let value = to_bytes(&pair.get_item(1).map_err(|e| e.to_string())?, "result")?;
+                output.replies.push((Some(key), value));
+            }
+        }
+        Some(result) => output.replies.push((None, to_bytes(&result, "result")?)),
+        None if input.sample.is_none() => return Err("The script did not set a result".into()),
+        None => {}
+    }
+    Ok(output)
+}
+
+fn to_bytes(value: &Bound<'_, PyAny>, name: &str) -> Result<Vec<u8>, String> {
+    if let Ok(s) = value.cast::<PyString>() {
+        s.to_str().map(|s| s.as_bytes().to_vec()).map_err(|e| e.to_string())
+    } else if let Ok(b) = value.cast::<PyBytes>() {
+        Ok(b.as_bytes().to_vec())
+    } else {
+        Err(format!(
+            "The script {} should be a string or bytes, not {}",
+            name,
+            value
+                .get_type()
+                .name()
+                .map(|n| n.to_string())
+                .unwrap_or_default()
+        ))
+    }
+}
+
+fn format_exception(py: Python<'_>, e: &PyErr) -> String {
+    match e.traceback(py).map(|tb| tb.format()) {
+        Some(Ok(traceback)) => format!("{}{}", traceback, e),
+        _ => e.to_string(),
+    }
+}
+
+fn interrupt(workers: &[Mutex<WorkerState>], id: u64) {
+    Python::attach(|_| {
+        for worker in workers {
+            let worker = worker.lock().unwrap();
+            if worker.job == Some(id) {
+                unsafe {
+                    pyo3::ffi::PyThreadState_SetAsyncExc(
+                        worker.thread_id,
+                        pyo3::ffi::PyExc_TimeoutError,
+                    );
+                }
+            }
+        }
+    });
+}
+
+// The session used by the zsak module, blocking calls being fine on the
+// worker threads.
+static SESSION: RwLock<Option<zenoh::Session>> = RwLock::new(None);
+
+// Installs the zsak module, shared by all the scripts and kept along with its
+// state when a script is reloaded.
+fn install_module(py: Python<'_>, session: &zenoh::Session) -> PyResult<()> {
+    *SESSION.write().unwrap() = Some(session.clone());
+    let modules = py.import("sys")?.getattr("modules")?;
+    if modules.contains("zsak")? {
+        return Ok(());
+    }
+    let module = PyModule::new(py, "zsak")?;
+    module.add_function(wrap_pyfunction!(put, &module)?)?;
+    module.add_function(wrap_pyfunction!(get, &module)?)?;
+    module.add_function(wrap_pyfunction!(delete, &module)?)?;
+    module.add_function(wrap_pyfunction!(log_message, &module)?)?;
+    module.add("state", PyDict::new(py))?;
+    modules.set_item("zsak", module)
+}
+
+fn session() -> PyResult<zenoh::Session> {
+    SESSION
+        .read()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| PyRuntimeError::new_err("No Zenoh session is available"))
+}
+
+/// Publishes a string or bytes value on a key expression.
+#[pyfunction]
+#[pyo3(signature = (key_expr, value, encoding = None))]
+fn put(
+    py: Python<'_>,
+    key_expr: String,
+    value: &Bound<'_, PyAny>,
+    encoding: Option<String>,
+) -> PyResult<()> {
+    let session = session()?;
+    let payload = to_bytes(value, "value").map_err(PyTypeError::new_err)?;
+    py.detach(|| {
+        let put = session.put(key_expr, payload);
+        match encoding {
+            Some(encoding) => put.encoding(encoding).wait(),
+            None => put.wait(),
+        }
+    })
+    .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+}
+
+/// Queries a selector, returning the replies as a list of (key, value) pairs.
+/// An error reply raises a RuntimeError carrying its payload.
+#[pyfunction]
+#[pyo3(signature = (selector, payload = None, timeout = None))]
+fn get<'py>(
+    py: Python<'py>,
+    selector: String,
+    payload: Option<&Bound<'py, PyAny>>,
+    timeout: Option<f64>,
+) -> PyResult<Vec<(String, Bound<'py, PyBytes>)>> {
+    let session = session()?;
+    let payload = payload
+        .map(|p| to_bytes(p, "payload"))
+        .transpose()
+        .map_err(PyTypeError::new_err)?;
+    let timeout = timeout
+        .map(Duration::try_from_secs_f64)
+        .transpose()
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    let replies = py
+        .detach(|| -> zenoh::Result<Vec<Reply>> {
+            let mut get = session.get(selector);
+            if let Some(payload) = payload {
+                get = get.payload(payload);
+            }
+            if let Some(timeout) = timeout {
+                get = get.timeout(timeout);
+            }
+            Ok(get.wait()?.iter().collect())
+        })
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+    replies
+        .iter()
+        .map(|reply| match reply.result() {
+            Ok(sample) => Ok((
+                sample.key_expr().to_string(),
+                PyBytes::new(py, &sample.payload().to_bytes()),
+            )),
+            Err(e) => Err(PyRuntimeError::new_err(
+                String::from_utf8_lossy(&e.payload().to_bytes()).to_string(),
+            )),
+        })
+        .collect()
+}
+
+/// Deletes a key expression.
+#[pyfunction]
+fn delete(py: Python<'_>, key_expr: String) -> PyResult<()> {
+    let session = session()?;
+    py.detach(|| session.delete(key_expr).wait())
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+}
+
+/// Logs a message through the zsak logger, at the error, warn, info, debug
+/// or trace level.
+#[pyfunction(name = "log")]
+#[pyo3(signature = (message, level = "info"))]
+fn log_message(message: &str, level: &str) -> PyResult<()> {
+    let level = level
+        .parse::<log::Level>()
+        .map_err(|_| PyValueError::new_err(format!("Invalid log level \"{}\"", level)))?;
+    log::log!(target: "zsak", level, "{}", message);
+    Ok(())
+}
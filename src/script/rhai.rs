@@ -0,0 +1,169 @@
+use super::{ScriptInput, ScriptOutput, ScriptResult};
+
+use rhai::{AST, Dynamic, Engine, EvalAltResult, Map, Scope};
+use std::cell::Cell;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+// Rhai scripts answering queries. Rhai being embedded, a script is compiled
+// once and run on the blocking threads of tokio, and the engine itself aborts
+// the calls running past the timeout.
+
+// Number of operations between two checks of the deadline.
+const DEADLINE_CHECK_PERIOD: u64 = 1024;
+
+thread_local! {
+    // Deadline of the script running on the current thread, if any.
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+#[derive(Clone)]
+pub struct RhaiRuntime {
+    engine: Arc<Engine>,
+    ast: Arc<Mutex<Arc<AST>>>,
+    permits: Arc<Semaphore>,
+    timeout: Duration,
+}
+
+pub fn new_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.on_progress(|ops| {
+        let expired = ops % DEADLINE_CHECK_PERIOD == 0
+            && DEADLINE.with(|d| d.get()).is_some_and(|d| Instant::now() > d);
+        expired.then_some(Dynamic::UNIT)
+    });
+    engine
+}
+
+impl RhaiRuntime {
+    pub fn new(code: &str, workers: usize, timeout: Duration) -> Result<Self, String> {
+        let engine = new_engine();
+        let ast = engine
+            .compile(code)
+            .map_err(|e| format!("Invalid script: {}", e))?;
+        Ok(Self {
+            engine: Arc::new(engine),
+            ast: Arc::new(Mutex::new(Arc::new(ast))),
+            permits: Arc::new(Semaphore::new(workers.max(1))),
+            timeout,
+        })
+    }
+
+    pub fn reload(&self, code: &str) -> Result<(), String> {
+        let ast = self
+            .engine
+            .compile(code)
+            .map_err(|e| format!("Invalid script: {}", e))?;
+        *self.ast.lock().unwrap() = Arc::new(ast);
+        Ok(())
+    }
+
+    pub async fn run(&self, input: ScriptInput) -> ScriptResult {
+        let _permit = self.permits.acquire().await.map_err(|e| e.to_string())?;
+        let engine = self.engine.clone();
+        let ast = self.ast.lock().unwrap().clone();
+        let timeout = self.timeout;
+        tokio::task::spawn_blocking(move || {
+            DEADLINE.with(|d| d.set(Some(Instant::now() + timeout)));
+            let result = execute(&engine, &ast, input);
+            DEADLINE.with(|d| d.set(None));
+            result.map_err(|e| match *e {
+                EvalAltResult::ErrorTerminated(..) => {
+                    format!("The script did not complete within {:?}", timeout)
+                }
+                e => e.to_string(),
+            })?
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+fn execute(engine: &Engine, ast: &AST, input: ScriptInput) -> Result<ScriptResult, Box<EvalAltResult>> {
+    let mut scope = Scope::new();
+    scope.push("key_expr", input.key_expr);
+    scope.push(
+        "parameters",
+        input
+            .parameters
+            .into_iter()
+            .map(|(name, value)| (name.into(), Dynamic::from(value)))
+            .collect::<Map>(),
+    );
+    scope.push("payload", Dynamic::from_blob(input.payload));
+    scope.push("encoding", input.encoding.map(Dynamic::from).unwrap_or(Dynamic::UNIT));
+    scope.push(
+        "attachment",
+        input
+            .attachment
+            .map(Dynamic::from_blob)
+            .unwrap_or(Dynamic::UNIT),
+    );
+    let hook = input.sample.is_some();
+    if let Some(sample) = input.sample {
+        scope.push("kind", sample.kind);
+        scope.push(
+            "timestamp",
+            sample.timestamp.map(Dynamic::from).unwrap_or(Dynamic::UNIT),
+        );
+    }
+    for name in ["result", "result_encoding", "result_attachment", "error"] {
+        scope.push(name, Dynamic::UNIT);
+    }
+    engine.run_ast_with_scope(&mut scope, ast)?;
+    Ok(output(&scope, hook))
+}
+
+fn output(scope: &Scope, hook: bool) -> ScriptResult {
+    let local = |name: &str| scope.get_value::<Dynamic>(name).filter(|v| !v.is_unit());
+    let mut output = ScriptOutput {
+        encoding: local("result_encoding")
+            .map(|e| e.into_string())
+            .transpose()
+            .map_err(|t| format!("The script result_encoding should be a string, not {}", t))?,
+        attachment: local("result_attachment")
+            .map(|a| to_bytes(a, "result_attachment"))
+            .transpose()?,
+        error: local("error").map(|e| to_bytes(e, "error")).transpose()?,
+        ..Default::default()
+    };
+    if output.error.is_some() {
+        return Ok(output);
+    }
+
+    match local("result") {
+        Some(result) if result.is_array() => {
+            for item in result.into_array().unwrap() {
+                let pair = item
+                    .into_array()
+                    .ok()
+                    .filter(|p| p.len() == 2)
+                    .ok_or_else(|| "The script result array should hold [key, value] pairs".to_string())?;
+                let [key, value]: [Dynamic; 2] = pair.try_into().unwrap();
+                let key = key
+                    .into_string()
+                    .map_err(|_| "The script result keys should be strings".to_string())?;
+                output.replies.push((Some(key), to_bytes(value, "result")?));
+            }
+        }
+        Some(result) => output.replies.push((None, to_bytes(result, "result")?)),
+        None if !hook => return Err("The script did not set a result".into()),
+        None => {}
+    }
+    Ok(output)
+}
+
+fn to_bytes(value: Dynamic, name: &str) -> Result<Vec<u8>, String> {
+    if value.is_string() {
+        Ok(value.into_string().unwrap().into_bytes())
+    } else if value.is_blob() {
+        Ok(value.into_blob().unwrap())
+    } else {
+        Err(format!(
+            "The script {} should be a string or a blob, not {}",
+            name,
+            value.type_name()
+        ))
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+// Helpers to build selectors, i.e. `key/expr?name=value;other=value`, out of
+// structured parameters rather than hand-written selector syntax.
+
+const TIME_PARAMETER: &str = "_time";
+
+// Characters that would otherwise be taken as selector delimiters.
+fn escape(s: &str, reserved: &[char]) -> String {
+    s.chars().fold(String::with_capacity(s.len()), |mut out, c| {
+        if c == '%' || reserved.contains(&c) {
+            out.push_str(&format!("%{:02X}", c as u32));
+        } else {
+            out.push(c);
+        }
+        out
+    })
+}
+
+/// Decodes the `%XX` sequences of a selector parameter name or value, such as
+/// the ones written by `build_selector`. Malformed sequences are kept as is.
+pub fn unescape(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok());
+        match hex {
+            Some(b) if bytes[i] == b'%' => {
+                out.push(b);
+                i += 3;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+pub fn build_selector(key_expr: &str, parameters: &BTreeMap<String, String>) -> String {
+    if parameters.is_empty() {
+        return key_expr.to_string();
+    }
+    let params = parameters
+        .iter()
+        .map(|(name, value)| {
+            // Time ranges are interpreted by zenoh and must be kept verbatim
+            if name == TIME_PARAMETER {
+                format!("{}={}", name, value)
+            } else if value.is_empty() {
+                escape(name, &[';', '=', '#'])
+            } else {
+                format!(
+                    "{}={}",
+                    escape(name, &[';', '=', '#']),
+                    escape(value, &[';', '#'])
+                )
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(";");
+    if key_expr.contains('?') {
+        format!("{};{}", key_expr, params)
+    } else {
+        format!("{}?{}", key_expr, params)
+    }
+}
+
+/// Parses a `name=value` pair as given to `--param`.
+pub fn parse_parameter(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some(("", _)) => Err(format!("Missing parameter name in \"{}\"", s)),
+        Some((name, value)) => Ok((name.to_string(), value.to_string())),
+        None if s.is_empty() => Err("Empty parameter".into()),
+        None => Ok((s.to_string(), String::new())),
+    }
+}
+
+/// Builds the value of the `_time` parameter out of optional bounds. Bounds
+/// can either be zenoh time expressions, e.g. `now(-1h)` or an RFC3339 date,
+/// or plain durations, e.g. `1h`, that are taken as relative to now.
+pub fn time_range(since: Option<&str>, until: Option<&str>) -> Option<String> {
+    if since.is_none() && until.is_none() {
+        return None;
+    }
+    let bound = |b: Option<&str>| b.map(time_bound).unwrap_or_default();
+    Some(format!("[{}..{}]", bound(since), bound(until)))
+}
+
+fn time_bound(b: &str) -> String {
+    let b = b.trim();
+    let unit = b.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let is_duration = unit.len() < b.len()
+        && ["u", "ms", "s", "m", "h", "d", "w"].contains(&unit);
+    if is_duration {
+        format!("now(-{})", b)
+    } else {
+        b.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_round_trip() {
+        for s in ["plain", "a;b", "x=1#frag", "100%", "%41", "é;ü"] {
+            assert_eq!(unescape(&escape(s, &[';', '=', '#'])), s);
+        }
+    }
+
+    #[test]
+    fn unescape_malformed() {
+        assert_eq!(unescape("100%"), "100%");
+        assert_eq!(unescape("%+1%zz%4"), "%+1%zz%4");
+        assert_eq!(unescape("%3b%3B"), ";;");
+    }
+
+    #[test]
+    fn selector_parameters() {
+        let params = BTreeMap::from([
+            ("q".to_string(), "a;b".to_string()),
+            ("flag".to_string(), String::new()),
+        ]);
+        assert_eq!(build_selector("demo/**", &params), "demo/**?flag;q=a%3Bb");
+        assert_eq!(
+            build_selector("demo/**?x=1", &params),
+            "demo/**?x=1;flag;q=a%3Bb"
+        );
+    }
+}
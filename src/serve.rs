@@ -0,0 +1,112 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Mapping of a directory onto a key space, each file being the value of the
+// key made of its path relative to the directory, or of a data file holding
+// the value of each key.
+
+/// Guesses the encoding of a file out of its extension.
+pub fn encoding_for(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "json" => "application/json",
+        "json5" => "application/json5",
+        "yaml" | "yml" => "application/yaml",
+        "toml" => "application/toml",
+        "xml" => "application/xml",
+        "cbor" => "application/cbor",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "js" => "text/javascript",
+        "txt" | "log" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "py" => "text/x-python",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Lists the files under `root`, along with the keys they map to relatively
+/// to the prefix, sorted by key. Files whose path cannot be used as a key,
+/// e.g. holding wildcards, are skipped, as are symbolic links to directories,
+/// which could loop back to a parent.
+pub fn list_files(root: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut files = Vec::new();
+    collect_files(root, &mut Vec::new(), &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn collect_files(
+    dir: &Path,
+    chunks: &mut Vec<String>,
+    files: &mut Vec<(String, PathBuf)>,
+) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)?.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !is_chunk(&name) {
+            continue;
+        }
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        chunks.push(name);
+        if file_type.is_dir() {
+            collect_files(&path, chunks, files)?;
+        } else if path.is_file() {
+            files.push((chunks.join("/"), path));
+        }
+        chunks.pop();
+    }
+    Ok(())
+}
+
+/// Reads a JSON5 object mapping concrete keys to their values, along with the
+/// encoding of each value. Strings are served as is, other values as JSON.
+pub fn parse_data(content: &str) -> Result<BTreeMap<String, (String, &'static str)>, String> {
+    let doc: Value = json5::from_str(content).map_err(|e| e.to_string())?;
+    let Value::Object(entries) = doc else {
+        return Err("The data should be an object mapping keys to values".into());
+    };
+    entries
+        .into_iter()
+        .map(|(key, value)| {
+            if !key.split('/').all(is_chunk) {
+                return Err(format!("\"{}\" is not a concrete key", key));
+            }
+            let value = match value {
+                Value::String(s) => (s, "text/plain"),
+                other => (other.to_string(), "application/json"),
+            };
+            Ok((key, value))
+        })
+        .collect()
+}
+
+fn is_chunk(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['*', '$', '?', '#'])
+}
+
+/// Joins a prefix and a key relative to it.
+pub fn join_key(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}/{}", prefix, key)
+    }
+}
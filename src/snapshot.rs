@@ -0,0 +1,197 @@
+use serde_json::{Map, Value, json};
+use std::io;
+use std::path::{Path, PathBuf};
+
+// On-disk snapshots of a key space. A snapshot is either a directory tree
+// mirroring the key hierarchy, where each key is a directory holding the
+// payload in `@value` and the rest of the sample in `@meta.json`, or a
+// single `.json` archive listing all the entries.
+
+const VALUE_FILE: &str = "@value";
+const META_FILE: &str = "@meta.json";
+
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotEntry {
+    pub key: String,
+    pub payload: Vec<u8>,
+    pub encoding: String,
+    pub timestamp: Option<String>,
+    pub attachment: Option<Vec<u8>>,
+}
+
+fn is_archive(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "json")
+}
+
+pub fn write_snapshot(path: &Path, entries: &[SnapshotEntry]) -> io::Result<()> {
+    if is_archive(path) {
+        let archive = Value::Array(entries.iter().map(entry_to_json).collect());
+        std::fs::write(path, serde_json::to_string_pretty(&archive)?)
+    } else {
+        for entry in entries {
+            let dir = key_dir(path, &entry.key)?;
+            std::fs::create_dir_all(&dir)?;
+            std::fs::write(dir.join(VALUE_FILE), &entry.payload)?;
+            let mut meta = entry_to_json(entry);
+            if let Some(meta) = meta.as_object_mut() {
+                meta.remove("key");
+                meta.remove("payload");
+            }
+            std::fs::write(dir.join(META_FILE), serde_json::to_string_pretty(&meta)?)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn read_snapshot(path: &Path) -> io::Result<Vec<SnapshotEntry>> {
+    if is_archive(path) {
+        let archive: Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        archive
+            .as_array()
+            .ok_or_else(|| invalid("The archive should be an array of entries"))?
+            .iter()
+            .map(|v| entry_from_json(v, None))
+            .collect()
+    } else {
+        let mut entries = Vec::new();
+        read_dir_entries(path, &mut Vec::new(), &mut entries)?;
+        Ok(entries)
+    }
+}
+
+fn read_dir_entries(
+    dir: &Path,
+    chunks: &mut Vec<String>,
+    entries: &mut Vec<SnapshotEntry>,
+) -> io::Result<()> {
+    let value = dir.join(VALUE_FILE);
+    if value.is_file() {
+        let meta: Value = match std::fs::read_to_string(dir.join(META_FILE)) {
+            Ok(meta) => serde_json::from_str(&meta)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => json!({}),
+            Err(e) => return Err(e),
+        };
+        let mut entry = entry_from_json(&meta, Some(std::fs::read(value)?))?;
+        entry.key = chunks.join("/");
+        entries.push(entry);
+    }
+    let mut subdirs = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .collect::<Vec<_>>();
+    subdirs.sort_by_key(|e| e.file_name());
+    for subdir in subdirs {
+        chunks.push(subdir.file_name().to_string_lossy().to_string());
+        read_dir_entries(&subdir.path(), chunks, entries)?;
+        chunks.pop();
+    }
+    Ok(())
+}
+
+pub(crate) fn key_dir(root: &Path, key: &str) -> io::Result<PathBuf> {
+    let mut dir = root.to_path_buf();
+    for chunk in key.split('/') {
+        if chunk.is_empty() || chunk == "." || chunk == ".." {
+            return Err(invalid(&format!("Cannot map key \"{}\" to a directory", key)));
+        }
+        dir.push(chunk);
+    }
+    Ok(dir)
+}
+
+// Payloads and attachments are kept as text when they are valid UTF-8, and
+// hex encoded otherwise.
+fn bytes_to_json(map: &mut Map<String, Value>, field: &str, bytes: &[u8]) {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => map.insert(field.into(), Value::String(s.into())),
+        Err(_) => map.insert(format!("{}_hex", field), Value::String(to_hex(bytes))),
+    };
+}
+
+fn bytes_from_json(v: &Value, field: &str) -> io::Result<Option<Vec<u8>>> {
+    if let Some(s) = v.get(field).and_then(|s| s.as_str()) {
+        Ok(Some(s.as_bytes().to_vec()))
+    } else if let Some(s) = v.get(format!("{}_hex", field)).and_then(|s| s.as_str()) {
+        from_hex(s).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+fn entry_to_json(entry: &SnapshotEntry) -> Value {
+    let mut map = Map::new();
+    map.insert("key".into(), Value::String(entry.key.clone()));
+    map.insert("encoding".into(), Value::String(entry.encoding.clone()));
+    if let Some(ref ts) = entry.timestamp {
+        map.insert("timestamp".into(), Value::String(ts.clone()));
+    }
+    bytes_to_json(&mut map, "payload", &entry.payload);
+    if let Some(ref attachment) = entry.attachment {
+        bytes_to_json(&mut map, "attachment", attachment);
+    }
+    Value::Object(map)
+}
+
+fn entry_from_json(v: &Value, payload: Option<Vec<u8>>) -> io::Result<SnapshotEntry> {
+    let payload = match payload {
+        Some(payload) => payload,
+        None => bytes_from_json(v, "payload")?
+            .ok_or_else(|| invalid(&format!("Missing payload in {}", v)))?,
+    };
+    Ok(SnapshotEntry {
+        key: v
+            .get("key")
+            .and_then(|k| k.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        payload,
+        encoding: v
+            .get("encoding")
+            .and_then(|e| e.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        timestamp: v
+            .get("timestamp")
+            .and_then(|t| t.as_str())
+            .map(|t| t.to_string()),
+        attachment: bytes_from_json(v, "attachment")?,
+    })
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> io::Result<Vec<u8>> {
+    if !s.is_ascii() || !s.len().is_multiple_of(2) {
+        return Err(invalid("Invalid hex string"));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| invalid(&e.to_string())))
+        .collect()
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Replaces the `from` prefix of a key with `to`. Keys outside of `from` are
+/// returned unchanged.
+pub fn rewrite_prefix(key: &str, from: Option<&str>, to: Option<&str>) -> String {
+    let from = from.map(|f| f.trim_end_matches('/')).unwrap_or_default();
+    let to = to.map(|t| t.trim_end_matches('/')).unwrap_or_default();
+    let rest = if from.is_empty() {
+        Some(key)
+    } else if key == from {
+        Some("")
+    } else {
+        key.strip_prefix(from).and_then(|r| r.strip_prefix('/'))
+    };
+    match rest {
+        Some("") => to.to_string(),
+        Some(rest) if to.is_empty() => rest.to_string(),
+        Some(rest) => format!("{}/{}", to, rest),
+        None => key.to_string(),
+    }
+}
@@ -0,0 +1,63 @@
+use crate::types::SampleContext;
+
+use jaq_core::load::{Arena, File, Loader};
+use jaq_core::{Compiler, Ctx, Filter, Native, RcIter};
+use jaq_json::Val;
+
+// jq-like transformations applied to JSON payloads before they are printed.
+// Besides the payload itself (bound to `.`), the expression can refer to
+// the sample context through the `$key`, `$kind`, `$timestamp` and
+// `$encoding` variables, e.g. `{key: $key, temp: .temperature}`.
+
+const TRANSFORM_VARS: [&str; 4] = ["$key", "$kind", "$timestamp", "$encoding"];
+
+pub struct Transform {
+    expr: String,
+    filter: Filter<Native<Val>>,
+}
+
+impl Transform {
+    pub fn new(expr: &str) -> Result<Self, String> {
+        let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+        let arena = Arena::default();
+        let modules = loader
+            .load(&arena, File { code: expr, path: () })
+            .map_err(|errs| format!("Unable to parse transform \"{}\": {:?}", expr, errs))?;
+        let filter = Compiler::default()
+            .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+            .with_global_vars(TRANSFORM_VARS)
+            .compile(modules)
+            .map_err(|errs| format!("Unable to compile transform \"{}\": {:?}", expr, errs))?;
+        Ok(Self {
+            expr: expr.to_string(),
+            filter,
+        })
+    }
+
+    /// Applies the transformation to a payload, returning one string per
+    /// output produced by the expression. Payloads that are not valid JSON
+    /// are handed to the expression as a JSON string. An empty result means
+    /// the expression filtered the sample out (e.g. `select(.temp > 20)`).
+    pub fn apply(&self, payload: &str, ctx: &SampleContext) -> Result<Vec<String>, String> {
+        let input = serde_json::from_str::<serde_json::Value>(payload)
+            .unwrap_or_else(|_| serde_json::Value::String(payload.to_string()));
+        let vars = [
+            Val::from(ctx.key.clone()),
+            Val::from(ctx.kind.clone()),
+            ctx.timestamp
+                .clone()
+                .map(Val::from)
+                .unwrap_or(Val::Null),
+            Val::from(ctx.encoding.clone()),
+        ];
+        let inputs = RcIter::new(core::iter::empty());
+        self.filter
+            .run((Ctx::new(vars, &inputs), Val::from(input)))
+            .map(|r| match r {
+                Ok(Val::Str(s)) => Ok(s.to_string()),
+                Ok(v) => Ok(v.to_string()),
+                Err(e) => Err(format!("Transform \"{}\" failed: {}", self.expr, e)),
+            })
+            .collect()
+    }
+}
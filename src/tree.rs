@@ -0,0 +1,121 @@
+use colored::Colorize;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+// In-memory trie of the keys observed under a prefix, indexed by key chunk.
+
+const MAX_VALUE_LEN: usize = 48;
+
+pub struct KeyEntry {
+    pub value: String,
+    pub updates: u64,
+    pub last_update: Instant,
+}
+
+#[derive(Default)]
+pub struct KeyTree {
+    children: BTreeMap<String, KeyTree>,
+    entry: Option<KeyEntry>,
+}
+
+impl KeyTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, key: &str, value: String) {
+        let node = key
+            .split('/')
+            .fold(self, |node, chunk| node.children.entry(chunk.to_string()).or_default());
+        match node.entry {
+            Some(ref mut entry) => {
+                entry.value = value;
+                entry.updates += 1;
+                entry.last_update = Instant::now();
+            }
+            None => {
+                node.entry = Some(KeyEntry {
+                    value,
+                    updates: 1,
+                    last_update: Instant::now(),
+                })
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        let chunks: Vec<&str> = key.split('/').collect();
+        self.remove_chunks(&chunks);
+    }
+
+    // Returns true when the node is left empty and can be pruned.
+    fn remove_chunks(&mut self, chunks: &[&str]) -> bool {
+        match chunks.split_first() {
+            None => self.entry = None,
+            Some((chunk, rest)) => {
+                if let Some(child) = self.children.get_mut(*chunk)
+                    && child.remove_chunks(rest)
+                {
+                    self.children.remove(*chunk);
+                }
+            }
+        }
+        self.entry.is_none() && self.children.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.children.values().map(|c| c.len()).sum::<usize>() + self.entry.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.render_into(&mut out, 0);
+        out
+    }
+
+    fn render_into(&self, out: &mut String, depth: usize) {
+        for (chunk, child) in &self.children {
+            let indent = "  ".repeat(depth);
+            match child.entry {
+                Some(ref entry) => out.push_str(&format!(
+                    "{}{} = {} {}\n",
+                    indent,
+                    chunk.bold(),
+                    truncate(&entry.value),
+                    format!(
+                        "(updates: {}, age: {})",
+                        entry.updates,
+                        format_age(entry.last_update.elapsed())
+                    )
+                    .dimmed()
+                )),
+                None => out.push_str(&format!("{}{}/\n", indent, chunk)),
+            }
+            child.render_into(out, depth + 1);
+        }
+    }
+}
+
+fn truncate(value: &str) -> String {
+    let value = value.replace('\n', " ");
+    if value.chars().count() > MAX_VALUE_LEN {
+        value.chars().take(MAX_VALUE_LEN).collect::<String>() + "..."
+    } else {
+        value
+    }
+}
+
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m{}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
@@ -1,5 +1,11 @@
+use crate::fault::Delay;
+use crate::selector::build_selector;
+
+use std::collections::BTreeMap;
+use std::time::Duration;
 use zenoh::query::{ConsolidationMode, QueryTarget};
-use zenoh::qos::Priority;
+use zenoh::qos::{CongestionControl, Priority};
+use zenoh::sample::{Sample, SampleKind};
 
 #[derive(Clone, Debug)]
 pub struct PublishParams {
@@ -31,6 +37,54 @@ pub struct SubscribeParams {
     pub key_expr: String,
 }
 
+#[derive(Clone, Debug)]
+pub struct SampleMetadata {
+    pub kind: SampleKind,
+    pub timestamp: Option<String>,
+    pub encoding: String,
+    pub priority: Priority,
+    pub congestion_control: CongestionControl,
+    pub express: bool,
+    pub source_id: Option<String>,
+    pub source_sn: Option<u32>,
+    pub size: usize,
+}
+
+impl Default for SampleMetadata {
+    fn default() -> Self {
+        Self {
+            kind: SampleKind::Put,
+            timestamp: None,
+            encoding: String::new(),
+            priority: Priority::Data,
+            congestion_control: CongestionControl::Drop,
+            express: false,
+            source_id: None,
+            source_sn: None,
+            size: 0,
+        }
+    }
+}
+
+impl From<&Sample> for SampleMetadata {
+    fn from(sample: &Sample) -> Self {
+        Self {
+            kind: sample.kind(),
+            timestamp: sample.timestamp().map(|ts| ts.to_string()),
+            encoding: sample.encoding().to_string(),
+            priority: sample.priority(),
+            congestion_control: sample.congestion_control(),
+            express: sample.express(),
+            source_id: sample
+                .source_info()
+                .source_id()
+                .map(|id| id.zid().to_string()),
+            source_sn: sample.source_info().source_sn(),
+            size: sample.payload().len(),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct QueryParams {
     pub query_expr: String,
@@ -38,6 +92,8 @@ pub struct QueryParams {
     pub attachment: Option<String>,
     pub target: QueryTarget,
     pub consolidation: ConsolidationMode,
+    pub parameters: BTreeMap<String, String>,
+    pub timeout: Option<Duration>,
 }
 
 impl Default for QueryParams {
@@ -48,17 +104,34 @@ impl Default for QueryParams {
             attachment: None,
             target: QueryTarget::BestMatching,
             consolidation: ConsolidationMode::None,
+            parameters: BTreeMap::new(),
+            timeout: None,
         }
     }
 }
 
+impl QueryParams {
+    pub fn selector(&self) -> String {
+        build_selector(&self.query_expr, &self.parameters)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct QueryableParams {
     pub key_expr: String,
     pub reply: String,
     pub complete: bool,
     pub exec_script: bool,
+    pub engine: Option<String>,
+    pub exec: Option<String>,
     pub packages_path: Option<String>,
+    pub reply_error: bool,
+    pub workers: usize,
+    pub timeout: Duration,
+    // Faults injected for resilience testing
+    pub delay: Option<Delay>,
+    pub error_rate: f64,
+    pub drop_rate: f64,
 }
 
 impl Default for QueryableParams {
@@ -68,7 +141,15 @@ impl Default for QueryableParams {
             reply: String::new(),
             complete: false,
             exec_script: false,
+            engine: None,
+            exec: None,
             packages_path: None,
+            reply_error: false,
+            workers: 4,
+            timeout: Duration::from_secs(10),
+            delay: None,
+            error_rate: 0.0,
+            drop_rate: 0.0,
         }
     }
 }
@@ -134,6 +215,15 @@ pub struct LivelinessParams {
     pub action: LivelinessAction,
 }
 
+// Context of a received sample, handed to transformations and hooks
+#[derive(Clone, Debug, Default)]
+pub struct SampleContext {
+    pub key: String,
+    pub kind: String,
+    pub timestamp: Option<String>,
+    pub encoding: String,
+}
+
 // Events emitted by streaming operations
 #[derive(Clone, Debug)]
 pub enum ZenohEvent {
@@ -141,12 +231,19 @@ pub enum ZenohEvent {
         key: String,
         value: String,
         attachment: Option<String>,
+        meta: SampleMetadata,
         n: u64,
     },
     QueryIn {
         key: String,
         n: u64,
     },
+    ReplyError {
+        replier: Option<String>,
+        value: String,
+        encoding: String,
+        n: u64,
+    },
     LivelinessJoin(String),
     LivelinessLeave(String),
     Error(String),